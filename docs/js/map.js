@@ -103,6 +103,16 @@ function RemoveViewer() {
     }
 }
 
+// Marker position: the canonical location plus any display offset used to
+// spread out co-located markers.
+APP.display_position = function (element) {
+    let offset = element.display_offset || { lat: 0, lng: 0 };
+    return {
+        lat: Number(element.lat) + offset.lat,
+        lng: Number(element.lng) + offset.lng,
+    };
+};
+
 APP.toggle_markers = function (array, value) {
     array.forEach((element) => {
         element.setVisible(value);
@@ -171,34 +181,47 @@ APP.init = async function () {
     let data = await $.getJSON(`data/season_${APP.year}.json`, () => {});
     APP.data = data;
 
-    let locations_firstmap = await $.getJSON(
-        "https://firstmap.github.io/data/custom_locations.json"
-    );
-
-    for (const key in locations_firstmap) {
-        if (locations_firstmap.hasOwnProperty(key)) {
-            const element = locations_firstmap[key];
-            let data_key = "frc" + String(key);
-            if (data.teams.hasOwnProperty(data_key)) {
-                data.teams[data_key].lat = element.lat;
-                data.teams[data_key].lng = element.lng;
-                // console.log("Updated: " + data_key);
-            }
+    // Season files generated with the external resolution step already have
+    // their final locations and record a location_source for each team. Only
+    // older files still get the third-party locations patched in here.
+    let legacy = true;
+    for (const key in data.teams) {
+        if (data.teams.hasOwnProperty(key) && data.teams[key].location_source) {
+            legacy = false;
+            break;
         }
     }
 
-    let locations_frcmap = await $.getJSON(
-        "https://raw.githubusercontent.com/franspaco/frc_season_map/refs/heads/master/locations/teams.json"
-    );
-
-    for (const key in locations_frcmap) {
-        if (locations_frcmap.hasOwnProperty(key)) {
-            const element = locations_frcmap[key];
-            let data_key = String(key);
-            if (data.teams.hasOwnProperty(data_key)) {
-                data.teams[data_key].lat = element.lat;
-                data.teams[data_key].lng = element.lng;
-                console.log(`Updated '${data_key}' from GitHub data.`);
+    if (legacy) {
+        let locations_firstmap = await $.getJSON(
+            "https://firstmap.github.io/data/custom_locations.json"
+        );
+
+        for (const key in locations_firstmap) {
+            if (locations_firstmap.hasOwnProperty(key)) {
+                const element = locations_firstmap[key];
+                let data_key = "frc" + String(key);
+                if (data.teams.hasOwnProperty(data_key)) {
+                    data.teams[data_key].lat = element.lat;
+                    data.teams[data_key].lng = element.lng;
+                    // console.log("Updated: " + data_key);
+                }
+            }
+        }
+
+        let locations_frcmap = await $.getJSON(
+            "https://raw.githubusercontent.com/franspaco/frc_season_map/refs/heads/master/locations/teams.json"
+        );
+
+        for (const key in locations_frcmap) {
+            if (locations_frcmap.hasOwnProperty(key)) {
+                const element = locations_frcmap[key];
+                let data_key = String(key);
+                if (data.teams.hasOwnProperty(data_key)) {
+                    data.teams[data_key].lat = element.lat;
+                    data.teams[data_key].lng = element.lng;
+                    console.log(`Updated '${data_key}' from GitHub data.`);
+                }
             }
         }
     }
@@ -223,10 +246,7 @@ APP.init = async function () {
             }
 
             var marker = new google.maps.Marker({
-                position: {
-                    lat: Number(element.lat),
-                    lng: Number(element.lng),
-                },
+                position: APP.display_position(element),
                 icon: marker_icon,
                 map: this.map,
                 title: title,
@@ -261,10 +281,7 @@ APP.init = async function () {
             element.visible = false;
             element.edges = [];
             var marker = new google.maps.Marker({
-                position: {
-                    lat: Number(element.lat),
-                    lng: Number(element.lng),
-                },
+                position: APP.display_position(element),
                 icon: APP.getMarker(element.rookie_year),
                 map: this.map,
                 title: `${element.nickname} (${element.team_number})`,
@@ -290,14 +307,8 @@ APP.init = async function () {
             }
             for (const event of element.events) {
                 var path = [
-                    {
-                        lat: Number(element.lat),
-                        lng: Number(element.lng),
-                    },
-                    {
-                        lat: Number(data.events[event].lat),
-                        lng: Number(data.events[event].lng),
-                    },
+                    APP.display_position(element),
+                    APP.display_position(data.events[event]),
                 ];
                 var len = google.maps.geometry.spherical.computeLength(path);
                 var edge = new google.maps.Polyline({
@@ -1,9 +1,11 @@
 use std::path::PathBuf;
 
 use chrono::Datelike;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use serde::Deserialize;
 
+use crate::geocoder::{layout::Arrangement, resolve::LocationSource};
+
 /// FRC Season Map Generator - Rust port
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -51,6 +53,196 @@ pub struct Cli {
     /// Path to TOML file containing API keys
     #[arg(short = 'k', long = "api-keys", default_value = "api-keys.toml")]
     pub api_keys: PathBuf,
+
+    /// Path to TOML file with pipeline settings
+    #[arg(short = 's', long = "settings", default_value = "frcmap.toml")]
+    pub settings: PathBuf,
+
+    /// Retry addresses that previously failed to geocode, ignoring the retry-after delay
+    #[arg(long)]
+    pub retry_failed: bool,
+
+    /// What to do (defaults to `generate`)
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the full pipeline and write the season data
+    Generate,
+    /// Inspect or edit the address-keyed geocode cache
+    GeocodeCache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Add, remove or ignore entries in the location override files
+    Overrides {
+        #[command(subcommand)]
+        action: OverrideAction,
+    },
+    /// Merge, prune, export or import location archives
+    Archive {
+        #[command(subcommand)]
+        action: ArchiveAction,
+    },
+    /// List teams whose location in the season output looks wrong for their
+    /// stated city, state and country
+    Outliers {
+        /// Flag teams further than this from their postal code or city centroid
+        #[arg(long, default_value_t = 50.0)]
+        max_distance_km: f64,
+        /// Also write the flagged teams to this JSON file
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// List teams whose archived location moved between consecutive seasons
+    Stability {
+        /// Only compare this season's archive with the one before it
+        #[arg(long)]
+        season: Option<u32>,
+        /// Report moves longer than this [default: `max_move_km` from the settings]
+        #[arg(long)]
+        max_move_km: Option<f64>,
+        /// Also write the moves to this JSON file
+        #[arg(long)]
+        report: Option<PathBuf>,
+    },
+    /// Check override files, archives, settings and other data files for mistakes
+    Validate {
+        /// Directory whose other JSON, TOML and YAML files are checked for syntax errors
+        #[arg(long, default_value = ".")]
+        root: PathBuf,
+        /// Fail on warnings too
+        #[arg(long)]
+        strict: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CacheAction {
+    /// Show entry counts and age per provider
+    Stats,
+    /// List cached results, optionally filtered by address substring
+    List {
+        #[arg(long)]
+        contains: Option<String>,
+    },
+    /// Show every cached result for an address
+    Show { address: String },
+    /// List addresses that failed to geocode, for manual override work
+    Failures,
+    /// Drop an address, its place details and any recorded failure from the cache so it is
+    /// geocoded again
+    Remove { address: String },
+}
+
+/// Whether a command works on team or event locations.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationKind {
+    Teams,
+    Events,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum OverrideAction {
+    /// Add or update the location of a team or event
+    Add {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        /// Team number/key (`254`, `frc254`) or event key
+        key: String,
+        /// Coordinates (`37.5 -122.3`, `37°30'21"N 122°17'22"W`), a Google Maps
+        /// URL, a `geo:` URI or a Plus Code (`849VCWC8+R9`, `CWC8+R9 Mountain View, CA`)
+        #[arg(required = true, num_args = 1.., allow_negative_numbers = true)]
+        coords: Vec<String>,
+        /// First season the override applies to
+        #[arg(long)]
+        valid_from: Option<u32>,
+        /// Last season the override applies to
+        #[arg(long)]
+        valid_until: Option<u32>,
+        /// Why the override is needed
+        #[arg(long)]
+        reason: Option<String>,
+        /// Where the coordinates came from
+        #[arg(long)]
+        source: Option<String>,
+    },
+    /// Remove a team or event from the override file
+    Remove {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        key: String,
+    },
+    /// Mark a team or event as ignored (or not) so it's left off the map
+    SetIgnore {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        key: String,
+        #[arg(default_value_t = true, action = clap::ArgAction::Set)]
+        ignore: bool,
+    },
+    /// List overrides that have expired, were bypassed by another source in the newest season
+    /// output, or whose key hasn't appeared in recent seasons
+    Stale {
+        /// Only check team or event overrides [default: both]
+        #[arg(long, value_enum)]
+        kind: Option<LocationKind>,
+        /// How many of the newest season output files count as recent
+        #[arg(long, default_value_t = 3)]
+        seasons: u32,
+    },
+}
+
+/// Coordinate file formats understood by `archive import`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// CSV with a header row naming key, latitude and longitude columns
+    Csv,
+    /// GeoJSON FeatureCollection of Points with a key property
+    Geojson,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ArchiveAction {
+    /// Merge every yearly archive, newest first, into one consolidated archive
+    Merge {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        /// Where to write the merged archive [default: <archive>/all_<kind>_locations_merged.json]
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Drop keys from the newest archive that haven't appeared in the last N seasons
+    Prune {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        /// Number of most recent seasons (per the season output files) a key must appear in
+        #[arg(long)]
+        seasons: u32,
+        /// Only list the keys that would be pruned
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write an archive as a location override file
+    Export {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        /// Export a single season instead of the merged view of all seasons
+        #[arg(long)]
+        season: Option<u32>,
+        output: PathBuf,
+    },
+    /// Import coordinates from another tool into the newest archive
+    Import {
+        #[arg(long, value_enum, default_value = "teams")]
+        kind: LocationKind,
+        #[arg(long, value_enum, default_value = "csv")]
+        format: ImportFormat,
+        input: PathBuf,
+    },
 }
 
 /// API keys loaded from TOML config file
@@ -71,3 +263,203 @@ impl ApiKeys {
         Ok(keys)
     }
 }
+
+/// Pipeline settings loaded from an optional TOML file. Every section falls
+/// back to its defaults when missing.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub geocoding: GeocodingSettings,
+    pub resolution: ResolutionSettings,
+    pub gazetteer: GazetteerSettings,
+    pub layout: LayoutSettings,
+    pub external: ExternalSettings,
+    pub stability: StabilitySettings,
+    pub locality: LocalitySettings,
+}
+
+/// Limits on paid Google Maps geocode calls.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct GeocodingSettings {
+    /// Maximum paid geocode calls in a single run (unlimited if unset).
+    pub max_calls_per_run: Option<u32>,
+    /// Maximum paid geocode calls per UTC day across runs (unlimited if unset).
+    pub max_calls_per_day: Option<u32>,
+    /// Days to wait before retrying an address the provider could not
+    /// resolve. `--retry-failed` sets this to 0.
+    pub retry_failed_after_days: u32,
+}
+
+impl Default for GeocodingSettings {
+    fn default() -> Self {
+        Self {
+            max_calls_per_run: None,
+            max_calls_per_day: None,
+            retry_failed_after_days: 30,
+        }
+    }
+}
+
+/// Ordered location sources tried for teams and events. The first source
+/// that yields a location wins.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ResolutionSettings {
+    pub teams: Vec<LocationSource>,
+    pub events: Vec<LocationSource>,
+}
+
+impl Default for ResolutionSettings {
+    fn default() -> Self {
+        use LocationSource::*;
+        Self {
+            teams: vec![
+                Override, External, Tba, Archive, PlaceId, Geocode, Gazetteer,
+            ],
+            events: vec![
+                Override, External, Tba, Archive, Venue, PlaceId, FirstApi, Geocode, Gazetteer,
+            ],
+        }
+    }
+}
+
+/// Third-party location override sources, used by the `external` resolution
+/// step. Earlier sources take priority over later ones.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ExternalSettings {
+    pub sources: Vec<ExternalSourceSettings>,
+    /// Locations for the same key further apart than this many meters, in
+    /// different sources or the override file, are reported as conflicts.
+    pub conflict_radius_m: f64,
+}
+
+impl Default for ExternalSettings {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+            conflict_radius_m: 1000.0,
+        }
+    }
+}
+
+/// One external source: an override-style file (JSON, TOML or YAML) read
+/// from `path` or downloaded from `url`.
+#[derive(Debug, Deserialize)]
+pub struct ExternalSourceSettings {
+    pub name: String,
+    #[serde(default = "default_external_kind")]
+    pub kind: LocationKind,
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+fn default_external_kind() -> LocationKind {
+    LocationKind::Teams
+}
+
+/// Cross-season stability check of team locations.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StabilitySettings {
+    /// Fail `generate` when a team moved since the previous season without
+    /// an address or source change to explain it.
+    pub gate: bool,
+    /// Moves up to this many kilometers are ignored.
+    pub max_move_km: f64,
+}
+
+impl Default for StabilitySettings {
+    fn default() -> Self {
+        Self {
+            gate: false,
+            max_move_km: 5.0,
+        }
+    }
+}
+
+/// Filling in missing team localities from their location.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LocalitySettings {
+    /// Reverse geocode teams without a city or country against the gazetteer.
+    pub fill_missing: bool,
+    /// Only use a gazetteer city within this many kilometers.
+    pub max_distance_km: f64,
+}
+
+impl Default for LocalitySettings {
+    fn default() -> Self {
+        Self {
+            fill_missing: false,
+            max_distance_km: 25.0,
+        }
+    }
+}
+
+/// Offline GeoNames gazetteer files. Files missing from `path` are
+/// downloaded once from the matching URL, if one is set.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct GazetteerSettings {
+    pub path: PathBuf,
+    pub postal_codes_url: Option<String>,
+    pub cities_url: Option<String>,
+    pub admin1_codes_url: Option<String>,
+}
+
+impl Default for GazetteerSettings {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("locations/gazetteer"),
+            postal_codes_url: None,
+            cities_url: None,
+            admin1_codes_url: None,
+        }
+    }
+}
+
+/// Placement of objects that share a location.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LayoutSettings {
+    /// Points closer than this many meters are treated as one location.
+    pub group_radius_m: f64,
+    pub arrangement: Arrangement,
+    /// Distance between neighbouring markers in a ring or spiral, in meters.
+    pub spacing_m: f64,
+    /// Seed mixed into every per-key jitter (only used by `jitter`); change
+    /// it to reshuffle all jittered markers at once.
+    pub jitter_seed: u64,
+    /// Furthest a jittered marker may move from its location, in meters.
+    pub jitter_radius_m: f64,
+}
+
+impl Default for LayoutSettings {
+    fn default() -> Self {
+        Self {
+            group_radius_m: 25.0,
+            arrangement: Arrangement::Ring,
+            spacing_m: 60.0,
+            jitter_seed: 0,
+            jitter_radius_m: 300.0,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &PathBuf) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(path).map_err(|e| {
+            anyhow::anyhow!("Failed to read settings from {}: {}", path.display(), e)
+        })?;
+        let settings: Settings = toml::from_str(&content)
+            .map_err(|e| anyhow::anyhow!("Failed to parse settings TOML: {}", e))?;
+        Ok(settings)
+    }
+}
@@ -10,8 +10,10 @@ use reqwest_middleware::ClientWithMiddleware;
 use serde_json::{Value, json};
 
 use crate::{
+    config::Settings,
     first_api::FirstApiClient,
-    geocoder::{FrcGeocoder, types::LocationDict},
+    fsio::{self, DirLock},
+    geocoder::{FrcGeocoder, external::ExternalSources, types::LocationDict},
     map_types::{EventData, TeamData},
     tba::TbaClient,
 };
@@ -33,9 +35,12 @@ impl FrcMap {
         first_token: &str,
         year: u32,
         archive: PathBuf,
+        cache: PathBuf,
         team_overrides: LocationDict,
         event_overrides: LocationDict,
+        external: ExternalSources,
         debug_path: PathBuf,
+        settings: &Settings,
     ) -> Self {
         let tba = TbaClient::new(Arc::clone(&client), tba_key);
         let first_api = FirstApiClient::new(Arc::clone(&client), first_token);
@@ -43,9 +48,12 @@ impl FrcMap {
             Arc::clone(&client),
             gmaps_key,
             archive,
+            &cache,
             team_overrides,
             event_overrides,
+            external,
             first_api,
+            settings,
         );
 
         std::fs::create_dir_all(&debug_path).ok();
@@ -64,8 +72,8 @@ impl FrcMap {
         let path = self.debug_path.join(format!("{}.json", name));
         match serde_json::to_string_pretty(data) {
             Ok(json) => {
-                if let Err(e) = std::fs::write(&path, json) {
-                    error!("Failed to write debug file {}: {}", path.display(), e);
+                if let Err(e) = fsio::write_atomic(&path, json) {
+                    error!("Failed to write debug file: {:#}", e);
                 }
             }
             Err(e) => error!("Failed to serialise debug data for {}: {}", name, e),
@@ -102,14 +110,21 @@ impl FrcMap {
         // 4. Geocode team locations
         self.geocoder
             .populate_team_locations(&mut teams, self.year)
-            .await;
+            .await?;
         self.debug_dump("teams_geocoded", &teams);
 
         // 5. Geocode event locations
         self.geocoder
             .populate_event_locations(&mut events, self.year)
-            .await;
+            .await?;
         self.debug_dump("events_geocoded", &events);
+        self.debug_dump("geocode_pending", &self.geocoder.pending_geocodes());
+        self.debug_dump("geocode_failures", &self.geocoder.geocode_failures());
+        self.debug_dump(
+            "archive_invalidated",
+            &self.geocoder.archive_invalidations(),
+        );
+        self.debug_dump("external_conflicts", &self.geocoder.external_conflicts());
 
         // 6. Get team→events mapping
         let team_events = self.tba.get_team_events(self.year).await?;
@@ -162,14 +177,14 @@ impl FrcMap {
         let pretty_path = output_dir.join(format!("season_{}_pretty.json", self.year));
         let compact_path = output_dir.join(format!("season_{}.json", self.year));
 
+        let _lock = DirLock::acquire(output_dir)?;
+
         let pretty = serde_json::to_string_pretty(data)?;
-        std::fs::write(&pretty_path, &pretty)
-            .with_context(|| format!("Failed to write {}", pretty_path.display()))?;
+        fsio::write_atomic(&pretty_path, &pretty)?;
         info!("Wrote {}", pretty_path.display());
 
         let compact = serde_json::to_string(data)?;
-        std::fs::write(&compact_path, &compact)
-            .with_context(|| format!("Failed to write {}", compact_path.display()))?;
+        fsio::write_atomic(&compact_path, &compact)?;
         info!("Wrote {}", compact_path.display());
 
         Ok(())
@@ -0,0 +1,108 @@
+//! Crash- and concurrency-safe file writes.
+
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{Context, Result as AnyhowResult};
+
+const LOCK_FILE: &str = ".frcmap.lock";
+
+/// Write `contents` to `path` through a temp file in the same directory that
+/// is synced and then renamed into place, so readers never see a partial file.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> AnyhowResult<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .with_context(|| format!("Not a file path: {}", path.display()))?
+        .to_string_lossy();
+    let tmp = dir.join(format!(".{}.{}.tmp", name, std::process::id()));
+
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        file.write_all(contents.as_ref())?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if result.is_err() {
+        fs::remove_file(&tmp).ok();
+    }
+    result.with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Exclusive lock on a directory, held until dropped. Keeps two runs from
+/// writing the same archive or output directory at once.
+///
+/// The lock is an OS advisory lock on a `.frcmap.lock` file, so the OS
+/// releases it when a run exits or crashes. The file itself is left in place
+/// and names the run that last held it.
+pub struct DirLock {
+    _file: File,
+}
+
+impl DirLock {
+    /// Take the lock on `dir`, failing if another run holds it.
+    pub fn acquire(dir: &Path) -> AnyhowResult<Self> {
+        let path = dir.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to create {}", path.display()))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = fs::read_to_string(&path).unwrap_or_default();
+                anyhow::bail!(
+                    "{} is locked by another run ({})",
+                    dir.display(),
+                    holder.trim()
+                );
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+        file.set_len(0)?;
+        writeln!(
+            file,
+            "pid {} since {}",
+            std::process::id(),
+            chrono::Utc::now().to_rfc3339()
+        )?;
+        Ok(Self { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_is_exclusive_until_dropped() {
+        let dir = std::env::temp_dir().join(format!("frcmap-lock-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let lock = DirLock::acquire(&dir).unwrap();
+        let err = DirLock::acquire(&dir).err().unwrap().to_string();
+        assert!(err.contains("locked by another run"), "{}", err);
+        assert!(
+            err.contains(&format!("pid {}", std::process::id())),
+            "{}",
+            err
+        );
+        drop(lock);
+        // A lock file left behind doesn't block the next run.
+        assert!(dir.join(LOCK_FILE).exists());
+        DirLock::acquire(&dir).unwrap();
+
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,20 @@
+//! Small spherical-geometry helpers shared by layout and validation code.
+
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Great-circle distance between two points, in meters.
+pub fn distance_m(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = (lat2 - lat1).to_radians();
+    let dlambda = (lng2 - lng1).to_radians();
+    let a = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+/// Move a point by `east_m`/`north_m` meters. Uses a local flat-earth
+/// approximation, which is plenty for offsets of a few hundred meters.
+pub fn offset_m(lat: f64, lng: f64, east_m: f64, north_m: f64) -> (f64, f64) {
+    let dlat = (north_m / EARTH_RADIUS_M).to_degrees();
+    let dlng = (east_m / (EARTH_RADIUS_M * lat.to_radians().cos().max(1e-6))).to_degrees();
+    (lat + dlat, lng + dlng)
+}
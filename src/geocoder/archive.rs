@@ -0,0 +1,771 @@
+//! Location archive I/O.
+//!
+//! Archives remember where every team and event was placed, and how, so
+//! later runs can reuse the location without another geocode. The current
+//! format (version 2) wraps the entries with a format version:
+//!
+//! ```json
+//! { "version": 2, "entries": { "frc254": { "lat": .., "lng": .., "source": "geocode", .. } } }
+//! ```
+//!
+//! Version 1 archives were a bare `{ "frc254": { "lat": .., "lng": .. } }`
+//! map; they are migrated on load with `source: "archive"` since their
+//! origin is unknown.
+//!
+//! The `archive` subcommand (merge, prune, export, import) lives at the
+//! bottom of this file.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result as AnyhowResult};
+use log::{info, warn};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{ArchiveAction, ImportFormat, LocationKind},
+    fsio,
+    geocoder::{
+        FrcGeocoder, coords,
+        layout::natural_cmp,
+        normalize,
+        overrides::{self, OverrideFile},
+        resolve::LocationSource,
+        types::Provenance,
+        validate,
+    },
+    map_types::{EventData, HasLocation, TeamData},
+};
+
+pub const ARCHIVE_VERSION: u32 = 2;
+
+/// Event archive shared by every season before archives were split per year.
+const LEGACY_EVENT_ARCHIVE: &str = "all_event_locations.json";
+pub(crate) const VENUE_REGISTRY: &str = "venues.json";
+
+/// Version of this tool, recorded on every newly resolved entry.
+pub const TOOL_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// One archived location with the provenance it was resolved with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub lat: f64,
+    pub lng: f64,
+    #[serde(flatten)]
+    pub provenance: Provenance,
+}
+
+pub type Archive = BTreeMap<String, ArchiveEntry>;
+
+/// An archived location dropped because the object's address changed since
+/// it was resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveInvalidation {
+    pub key: String,
+    pub archived_address: Option<String>,
+    pub current_address: Option<String>,
+    pub archived_lat: f64,
+    pub archived_lng: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveFile {
+    version: u32,
+    entries: Archive,
+}
+
+/// Version 1 entry: just a coordinate.
+#[derive(Debug, Deserialize)]
+struct LegacyEntry {
+    lat: Option<f64>,
+    lng: Option<f64>,
+}
+
+/// Parse archive content in any known format version.
+pub fn parse_archive(content: &str) -> AnyhowResult<Archive> {
+    let value: Value = serde_json::from_str(content)?;
+    match value.get("version").and_then(Value::as_u64) {
+        Some(v) if v == u64::from(ARCHIVE_VERSION) => {
+            let file: ArchiveFile = serde_json::from_value(value)?;
+            Ok(file.entries)
+        }
+        Some(v) => anyhow::bail!("Unsupported archive version {}", v),
+        None => migrate_v1(value),
+    }
+}
+
+fn migrate_v1(value: Value) -> AnyhowResult<Archive> {
+    let legacy: HashMap<String, LegacyEntry> = serde_json::from_value(value)?;
+    let archive: Archive = legacy
+        .into_iter()
+        .filter_map(|(key, entry)| {
+            Some((
+                key,
+                ArchiveEntry {
+                    lat: entry.lat?,
+                    lng: entry.lng?,
+                    provenance: Provenance::new(LocationSource::Archive),
+                },
+            ))
+        })
+        .collect();
+    info!(
+        "Migrated {} entries from a version 1 archive",
+        archive.len()
+    );
+    Ok(archive)
+}
+
+fn read_archive_file(path: &Path, label: &str) -> Archive {
+    match std::fs::read_to_string(path) {
+        Ok(content) => match parse_archive(&content) {
+            Ok(archive) => return archive,
+            Err(e) => warn!("Failed to parse {} archive: {}", label, e),
+        },
+        Err(e) => warn!("Failed to read {} archive: {}", label, e),
+    }
+    Archive::new()
+}
+
+/// Archive files in `archive_path` named `{prefix}_{year}.json`, by year.
+pub(crate) fn archive_years(archive_path: &Path, prefix: &str) -> BTreeMap<i64, PathBuf> {
+    let re = Regex::new(&format!(r"^{}_(\d{{4}})\.json$", prefix)).unwrap();
+    let mut years = BTreeMap::new();
+
+    if let Ok(entries) = std::fs::read_dir(archive_path) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if let Some(caps) = re.captures(&name)
+                && let Ok(y) = caps[1].parse::<i64>()
+            {
+                years.insert(y, entry.path());
+            }
+        }
+    }
+    years
+}
+
+pub fn read_team_archive(archive_path: &Path) -> Archive {
+    if let Some((max_year, path)) =
+        archive_years(archive_path, "all_team_locations").last_key_value()
+    {
+        info!(
+            "Using team location archive: {} (year {})",
+            path.display(),
+            *max_year
+        );
+        return read_archive_file(path, "team");
+    }
+    warn!(
+        "Team archive not available, geocoding will take a while and incur several API requests."
+    );
+    Archive::new()
+}
+
+/// Read every season's event archive into one map. Event keys include the
+/// season, so seasons never collide. The old shared
+/// `all_event_locations.json` is read first, if it is still around.
+pub fn read_event_archive(archive_path: &Path) -> Archive {
+    let mut archive = Archive::new();
+    let legacy = archive_path.join(LEGACY_EVENT_ARCHIVE);
+    if legacy.is_file() {
+        archive.extend(read_archive_file(&legacy, "event"));
+    }
+    for (year, path) in archive_years(archive_path, "all_event_locations") {
+        info!(
+            "Using event location archive: {} (year {})",
+            path.display(),
+            year
+        );
+        archive.extend(read_archive_file(&path, "event"));
+    }
+    if archive.is_empty() {
+        warn!(
+            "Event archive not available, geocoding will take a while and incur several API requests."
+        );
+    }
+    archive
+}
+
+// ── Venue registry ─────────────────────────────────────────────
+
+/// A venue seen at an earlier event, so later events held there can reuse
+/// its location.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VenueEntry {
+    pub lat: f64,
+    pub lng: f64,
+    /// Address the venue was registered under, for humans.
+    pub address: String,
+    /// Event keys held at this venue.
+    #[serde(default)]
+    pub events: BTreeSet<String>,
+}
+
+/// Venues keyed by `normalize::address_key` of the event address, which
+/// includes the venue name.
+pub type VenueRegistry = BTreeMap<String, VenueEntry>;
+
+pub fn read_venue_registry(archive_path: &Path) -> VenueRegistry {
+    let path = archive_path.join(VENUE_REGISTRY);
+    if !path.is_file() {
+        return VenueRegistry::new();
+    }
+    match std::fs::read_to_string(&path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(venues) => return venues,
+            Err(e) => warn!("Failed to parse venue registry: {}", e),
+        },
+        Err(e) => warn!("Failed to read venue registry: {}", e),
+    }
+    VenueRegistry::new()
+}
+
+/// Add every located event with a named venue to `venues`. Gazetteer
+/// centroids are skipped since they only place the city, not the venue.
+fn register_venues(venues: &mut VenueRegistry, events: &HashMap<String, EventData>) {
+    let mut keys: Vec<&String> = events.keys().collect();
+    keys.sort();
+    for key in keys {
+        let event = &events[key];
+        if !event.has_location() || event.is_ignored() || !event.has_venue() {
+            continue;
+        }
+        let Some(provenance) = event.provenance() else {
+            continue;
+        };
+        if provenance.source == LocationSource::Gazetteer {
+            continue;
+        }
+        let Some(address) = provenance.address.clone() else {
+            continue;
+        };
+        let entry = venues
+            .entry(normalize::address_key(&address))
+            .or_insert_with(|| VenueEntry {
+                lat: 0.0,
+                lng: 0.0,
+                address,
+                events: BTreeSet::new(),
+            });
+        entry.lat = event.lat().unwrap();
+        entry.lng = event.lng().unwrap();
+        entry.events.insert(key.clone());
+    }
+}
+
+/// Build archive entries for every located, non-ignored object. Entries
+/// keep the provenance they were resolved with, so a location reused from
+/// an older archive keeps its original source and date.
+pub(super) fn build_archive<T: HasLocation>(objects: &HashMap<String, T>) -> Archive {
+    objects
+        .iter()
+        .filter(|(_, v)| v.has_location() && !v.is_ignored())
+        .map(|(k, v)| {
+            let provenance = v
+                .provenance()
+                .cloned()
+                .unwrap_or_else(|| Provenance::new(LocationSource::Archive));
+            let entry = ArchiveEntry {
+                lat: v.lat().unwrap(),
+                lng: v.lng().unwrap(),
+                provenance,
+            };
+            (k.clone(), entry)
+        })
+        .collect()
+}
+
+/// Atomically write `entries` to `path` in the current archive format.
+pub fn write_archive(path: &Path, entries: Archive) -> AnyhowResult<()> {
+    let file = ArchiveFile {
+        version: ARCHIVE_VERSION,
+        entries,
+    };
+    let json = serde_json::to_string(&file).context("Failed to serialize archive")?;
+    fsio::write_atomic(path, json)
+}
+
+impl FrcGeocoder {
+    pub(super) fn save_team_archive(
+        &self,
+        teams: &HashMap<String, TeamData>,
+        year: u32,
+    ) -> AnyhowResult<()> {
+        let name = format!("all_team_locations_{}.json", year);
+        std::fs::create_dir_all(&self.archive_path)?;
+        write_archive(&self.archive_path.join(name), build_archive(teams))
+            .context("Failed to save team archive")
+    }
+
+    pub(super) fn save_event_archive(
+        &self,
+        events: &HashMap<String, EventData>,
+        year: u32,
+    ) -> AnyhowResult<()> {
+        let name = format!("all_event_locations_{}.json", year);
+        std::fs::create_dir_all(&self.archive_path)?;
+        write_archive(&self.archive_path.join(name), build_archive(events))
+            .context("Failed to save event archive")?;
+
+        let mut venues = self.venues.clone();
+        register_venues(&mut venues, events);
+        let json = serde_json::to_string(&venues).context("Failed to serialize venue registry")?;
+        fsio::write_atomic(&self.archive_path.join(VENUE_REGISTRY), json)
+            .context("Failed to save venue registry")
+    }
+}
+
+// ── `archive` subcommand ───────────────────────────────────────
+
+pub(crate) fn archive_prefix(kind: LocationKind) -> &'static str {
+    match kind {
+        LocationKind::Teams => "all_team_locations",
+        LocationKind::Events => "all_event_locations",
+    }
+}
+
+/// Archive files of `kind`, newest season first. The legacy shared event
+/// archive, which has no season, comes last.
+fn archive_files(archive_path: &Path, kind: LocationKind) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = archive_years(archive_path, archive_prefix(kind))
+        .into_values()
+        .rev()
+        .collect();
+    let legacy = archive_path.join(LEGACY_EVENT_ARCHIVE);
+    if kind == LocationKind::Events && legacy.is_file() {
+        files.push(legacy);
+    }
+    files
+}
+
+/// The archive file the pipeline writes to next: the newest season's, or
+/// `year`'s if there is none yet.
+fn newest_archive_file(archive_path: &Path, kind: LocationKind, year: u32) -> PathBuf {
+    archive_years(archive_path, archive_prefix(kind))
+        .into_values()
+        .next_back()
+        .unwrap_or_else(|| archive_path.join(format!("{}_{}.json", archive_prefix(kind), year)))
+}
+
+pub(crate) fn load_archive(path: &Path) -> AnyhowResult<Archive> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_archive(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Every archive of `kind` merged, keeping each key's newest entry.
+fn merged_archive(archive_path: &Path, kind: LocationKind) -> AnyhowResult<Archive> {
+    let files = archive_files(archive_path, kind);
+    anyhow::ensure!(
+        !files.is_empty(),
+        "No {} archives in {}",
+        archive_prefix(kind),
+        archive_path.display()
+    );
+    let mut merged = Archive::new();
+    for path in files {
+        for (key, entry) in load_archive(&path)? {
+            merged.entry(key).or_insert(entry);
+        }
+    }
+    Ok(merged)
+}
+
+/// The `teams`/`events` objects in each `season_{year}.json` output file.
+pub(crate) fn season_objects(
+    output_path: &Path,
+    kind: LocationKind,
+) -> AnyhowResult<BTreeMap<i64, Map<String, Value>>> {
+    let field = match kind {
+        LocationKind::Teams => "teams",
+        LocationKind::Events => "events",
+    };
+    let mut seasons = BTreeMap::new();
+    for (year, path) in archive_years(output_path, "season") {
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let mut data: Value = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        let objects = match data.get_mut(field).map(Value::take) {
+            Some(Value::Object(objects)) => objects,
+            _ => Map::new(),
+        };
+        seasons.insert(year, objects);
+    }
+    Ok(seasons)
+}
+
+/// Keys listed under `teams`/`events` in each `season_{year}.json` output file.
+pub(crate) fn season_keys(
+    output_path: &Path,
+    kind: LocationKind,
+) -> AnyhowResult<BTreeMap<i64, BTreeSet<String>>> {
+    Ok(season_objects(output_path, kind)?
+        .into_iter()
+        .map(|(year, objects)| (year, objects.into_iter().map(|(k, _)| k).collect()))
+        .collect())
+}
+
+/// Column or property names recognised as the key, latitude and longitude.
+const KEY_NAMES: &[&str] = &[
+    "key",
+    "team",
+    "team_key",
+    "team_number",
+    "event",
+    "event_key",
+    "id",
+    "name",
+];
+pub(crate) const LAT_NAMES: &[&str] = &["lat", "latitude"];
+pub(crate) const LNG_NAMES: &[&str] = &["lng", "lon", "long", "longitude"];
+
+/// Split one CSV line, honouring double-quoted fields.
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+fn parse_csv(content: &str) -> AnyhowResult<Vec<(String, f64, f64)>> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<String> = split_csv_line(lines.next().context("CSV file is empty")?)
+        .into_iter()
+        .map(|h| h.to_lowercase())
+        .collect();
+    // Like GeoJSON properties, the first name in the list that has a column wins.
+    let column = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| header.iter().position(|h| h == name))
+    };
+    let (Some(key_col), Some(lat_col), Some(lng_col)) =
+        (column(KEY_NAMES), column(LAT_NAMES), column(LNG_NAMES))
+    else {
+        anyhow::bail!(
+            "CSV header needs key, latitude and longitude columns, got: {}",
+            header.join(",")
+        );
+    };
+
+    let mut rows = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let fields = split_csv_line(line);
+        let get = |col: usize| fields.get(col).map(String::as_str).unwrap_or("");
+        match (get(lat_col).parse::<f64>(), get(lng_col).parse::<f64>()) {
+            (Ok(lat), Ok(lng)) => rows.push((get(key_col).to_string(), lat, lng)),
+            _ => warn!("Skipping CSV row {}: invalid coordinates", i + 2),
+        }
+    }
+    Ok(rows)
+}
+
+fn parse_geojson(content: &str) -> AnyhowResult<Vec<(String, f64, f64)>> {
+    let data: Value = serde_json::from_str(content)?;
+    let features = data
+        .get("features")
+        .and_then(Value::as_array)
+        .context("GeoJSON has no features array")?;
+
+    let mut rows = Vec::new();
+    for (i, feature) in features.iter().enumerate() {
+        let key = feature.get("properties").and_then(|p| {
+            KEY_NAMES.iter().find_map(|name| match p.get(*name)? {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                _ => None,
+            })
+        });
+        let coords = feature
+            .pointer("/geometry/coordinates")
+            .and_then(Value::as_array)
+            .filter(|_| feature.pointer("/geometry/type") == Some(&Value::from("Point")));
+        match (key, coords) {
+            (Some(key), Some(coords)) if coords.len() >= 2 => {
+                // GeoJSON positions are [longitude, latitude].
+                match (coords[1].as_f64(), coords[0].as_f64()) {
+                    (Some(lat), Some(lng)) => rows.push((key, lat, lng)),
+                    _ => warn!("Skipping feature {}: invalid coordinates", i),
+                }
+            }
+            _ => warn!("Skipping feature {}: not a keyed Point", i),
+        }
+    }
+    Ok(rows)
+}
+
+/// Handle the `archive` subcommand. `year` names the archive to create when
+/// there is none yet.
+pub fn run_command(
+    archive_path: &Path,
+    output_path: &Path,
+    year: u32,
+    action: &ArchiveAction,
+) -> AnyhowResult<()> {
+    let _lock = fsio::DirLock::acquire(archive_path)?;
+    match action {
+        ArchiveAction::Merge { kind, output } => {
+            let merged = merged_archive(archive_path, *kind)?;
+            let path = output.clone().unwrap_or_else(|| {
+                archive_path.join(format!("{}_merged.json", archive_prefix(*kind)))
+            });
+            let count = merged.len();
+            write_archive(&path, merged)?;
+            println!("Merged {} keys into {}", count, path.display());
+        }
+        ArchiveAction::Prune {
+            kind,
+            seasons,
+            dry_run,
+        } => {
+            anyhow::ensure!(*seasons > 0, "--seasons must be at least 1");
+            let by_season = season_keys(output_path, *kind)?;
+            anyhow::ensure!(
+                !by_season.is_empty(),
+                "No season output files in {}; refusing to prune everything",
+                output_path.display()
+            );
+            let recent: BTreeSet<&String> = by_season
+                .values()
+                .rev()
+                .take(*seasons as usize)
+                .flatten()
+                .collect();
+
+            let path = newest_archive_file(archive_path, *kind, year);
+            let mut archive = load_archive(&path)?;
+            let stale: Vec<String> = archive
+                .keys()
+                .filter(|k| !recent.contains(k))
+                .cloned()
+                .collect();
+            for key in &stale {
+                println!("{}", key);
+            }
+            if *dry_run {
+                println!(
+                    "Would prune {} of {} keys from {}",
+                    stale.len(),
+                    archive.len(),
+                    path.display()
+                );
+                return Ok(());
+            }
+            for key in &stale {
+                archive.remove(key);
+            }
+            write_archive(&path, archive)?;
+            println!("Pruned {} keys from {}", stale.len(), path.display());
+        }
+        ArchiveAction::Export {
+            kind,
+            season,
+            output,
+        } => {
+            let archive = match season {
+                Some(year) => load_archive(&archive_path.join(format!(
+                    "{}_{}.json",
+                    archive_prefix(*kind),
+                    year
+                )))?,
+                None => merged_archive(archive_path, *kind)?,
+            };
+            let comment = format!(
+                "Exported from the location archive on {}",
+                chrono::Utc::now().date_naive()
+            );
+            let mut file = OverrideFile::new(output, Some(comment.into()));
+            let mut keys: Vec<&String> = archive.keys().collect();
+            keys.sort_by(|a, b| natural_cmp(a, b));
+            for key in keys {
+                file.set_location(key, archive[key].lat, archive[key].lng)?;
+            }
+            file.save()?;
+            println!("Exported {} keys to {}", archive.len(), output.display());
+        }
+        ArchiveAction::Import {
+            kind,
+            format,
+            input,
+        } => {
+            let content = std::fs::read_to_string(input)
+                .with_context(|| format!("Failed to read {}", input.display()))?;
+            let rows = match format {
+                ImportFormat::Csv => parse_csv(&content)?,
+                ImportFormat::Geojson => parse_geojson(&content)?,
+            };
+
+            let path = newest_archive_file(archive_path, *kind, year);
+            let mut archive = if path.is_file() {
+                load_archive(&path)?
+            } else {
+                Archive::new()
+            };
+            let (mut added, mut updated) = (0, 0);
+            for (raw_key, lat, lng) in rows {
+                let key = overrides::normalize_key(&raw_key, *kind);
+                if key.is_empty() {
+                    warn!("Skipping row with an empty key");
+                    continue;
+                }
+                if let Some(problem) = validate::key_problem(&key, *kind) {
+                    warn!("Skipping '{}': {}", raw_key, problem);
+                    continue;
+                }
+                if let Err(e) = coords::validate(lat, lng) {
+                    warn!("Skipping '{}': {}", raw_key, e);
+                    continue;
+                }
+                let entry = ArchiveEntry {
+                    lat,
+                    lng,
+                    provenance: Provenance::resolved(LocationSource::Archive, None),
+                };
+                match archive.insert(key, entry) {
+                    Some(_) => updated += 1,
+                    None => added += 1,
+                }
+            }
+            std::fs::create_dir_all(archive_path)?;
+            write_archive(&path, archive)?;
+            println!(
+                "Imported {} new and {} updated keys into {}",
+                added,
+                updated,
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geocoder::types::{GeocodeMatch, GeocodeQuality};
+
+    #[test]
+    fn migrates_version_1() {
+        let archive = parse_archive(
+            r#"{"frc254": {"lat": 37.25, "lng": -121.96}, "frc1": {"lat": null, "lng": 1.0}}"#,
+        )
+        .unwrap();
+        assert_eq!(archive.keys().collect::<Vec<_>>(), ["frc254"]);
+        let entry = &archive["frc254"];
+        assert_eq!((entry.lat, entry.lng), (37.25, -121.96));
+        assert_eq!(entry.provenance, Provenance::new(LocationSource::Archive));
+    }
+
+    #[test]
+    fn round_trips_version_2() {
+        let provenance = Provenance::resolved(
+            LocationSource::Geocode,
+            Some("1 Main St, San Jose, CA, USA".into()),
+        )
+        .with_quality(GeocodeQuality {
+            match_source: GeocodeMatch::Address,
+            location_type: Some("ROOFTOP".into()),
+            partial_match: false,
+            formatted_address: None,
+        });
+        let archive = Archive::from([(
+            "frc254".to_string(),
+            ArchiveEntry {
+                lat: 37.25,
+                lng: -121.96,
+                provenance: provenance.clone(),
+            },
+        )]);
+
+        let path = std::env::temp_dir().join(format!("frcmap-archive-{}.json", std::process::id()));
+        write_archive(&path, archive).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert!(content.starts_with(r#"{"version":2,"#));
+
+        let read = parse_archive(&content).unwrap();
+        assert_eq!(read["frc254"].provenance, provenance);
+        assert_eq!((read["frc254"].lat, read["frc254"].lng), (37.25, -121.96));
+    }
+
+    #[test]
+    fn rejects_unknown_versions() {
+        let err = parse_archive(r#"{"version": 3, "entries": {}}"#).unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported archive version 3");
+    }
+
+    #[test]
+    fn parses_csv_columns_by_name() {
+        let rows = parse_csv(
+            "Name,Longitude,Latitude,Team\n\
+             \"Spartan Robotics, \"\"The Poofs\"\"\",-121.96,37.25,254\n\
+             Bad row,east,north,1\n\
+             \n\
+             Citrus Circuits,-121.8,38.5,1678\n",
+        )
+        .unwrap();
+        // `team` ranks above `name`, and the quoted comma stays in its field.
+        assert_eq!(
+            rows,
+            [
+                ("254".to_string(), 37.25, -121.96),
+                ("1678".to_string(), 38.5, -121.8)
+            ]
+        );
+        assert!(parse_csv("team,lat\n254,37.25\n").is_err());
+        assert!(parse_csv("").is_err());
+    }
+
+    #[test]
+    fn parses_geojson_points() {
+        let rows = parse_geojson(
+            r#"{"type": "FeatureCollection", "features": [
+                {"properties": {"team_number": 254},
+                 "geometry": {"type": "Point", "coordinates": [-121.96, 37.25]}},
+                {"properties": {"key": "frc1678"},
+                 "geometry": {"type": "LineString", "coordinates": [[0, 0], [1, 1]]}},
+                {"properties": {},
+                 "geometry": {"type": "Point", "coordinates": [0, 0]}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(rows, [("254".to_string(), 37.25, -121.96)]);
+        assert!(parse_geojson(r#"{"type": "Feature"}"#).is_err());
+    }
+
+    #[test]
+    fn import_starts_the_requested_season() {
+        let dir = std::env::temp_dir().join(format!("frcmap-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("teams.csv");
+        std::fs::write(&input, "team,lat,lng\n254,37.25,-121.96\nfrc0x,1,1\n").unwrap();
+        let action = ArchiveAction::Import {
+            kind: LocationKind::Teams,
+            format: ImportFormat::Csv,
+            input,
+        };
+        run_command(&dir, &dir, 2019, &action).unwrap();
+        let archive = load_archive(&dir.join("all_team_locations_2019.json")).unwrap();
+        std::fs::remove_dir_all(&dir).ok();
+        assert_eq!(archive.keys().collect::<Vec<_>>(), ["frc254"]);
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result as AnyhowResult;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::CacheAction,
+    fsio,
+    geocoder::{
+        normalize,
+        types::{GeocodeMatch, GeocodeQuality},
+    },
+};
+
+const CACHE_FILE: &str = "geocode_cache.json";
+const FAILURES_FILE: &str = "geocode_failures.json";
+
+/// A single cached geocode result with the metadata the provider returned.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedGeocode {
+    /// The address (or place id) as it was sent to the provider.
+    pub query: String,
+    #[serde(default)]
+    pub match_source: GeocodeMatch,
+    pub lat: f64,
+    pub lng: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted_address: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub place_id: Option<String>,
+    /// Provider-specific precision, e.g. Google's `ROOFTOP` or `APPROXIMATE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_type: Option<String>,
+    #[serde(default)]
+    pub partial_match: bool,
+    pub fetched_at: DateTime<Utc>,
+}
+
+impl CachedGeocode {
+    pub fn quality(&self) -> GeocodeQuality {
+        GeocodeQuality {
+            match_source: self.match_source,
+            location_type: self.location_type.clone(),
+            partial_match: self.partial_match,
+            formatted_address: self.formatted_address.clone(),
+        }
+    }
+}
+
+/// A query the provider could not resolve, remembered so hopeless addresses
+/// aren't retried (and paid for) on every run.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedGeocode {
+    /// The address (or place id) as it was sent to the provider.
+    pub query: String,
+    #[serde(default)]
+    pub match_source: GeocodeMatch,
+    /// Team or event keys that needed this query.
+    #[serde(default)]
+    pub keys: BTreeSet<String>,
+    pub reason: String,
+    pub failed_at: DateTime<Utc>,
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// Geocode results keyed by provider name, then by normalized address.
+/// Unlike the HTTP cache, keys don't depend on the request URL, so rotating
+/// an API key keeps every cached result valid.
+pub struct GeocodeCache {
+    path: PathBuf,
+    entries: BTreeMap<String, BTreeMap<String, CachedGeocode>>,
+    failures_path: PathBuf,
+    /// Failed queries, keyed like `entries`.
+    failures: BTreeMap<String, BTreeMap<String, FailedGeocode>>,
+    dirty: bool,
+}
+
+fn load_json<T: serde::de::DeserializeOwned + Default>(path: &Path, label: &str) -> T {
+    match std::fs::read_to_string(path) {
+        Ok(content) => match serde_json::from_str(&content) {
+            Ok(value) => value,
+            Err(e) => {
+                warn!("Failed to parse {}: {}", label, e);
+                T::default()
+            }
+        },
+        Err(_) => T::default(),
+    }
+}
+
+/// Cache key for an address, insensitive to case, accents, punctuation and
+/// whitespace.
+pub fn cache_key(address: &str) -> String {
+    normalize::address_key(address)
+}
+
+/// Key for a query of the given kind. Place ids are case-sensitive, so they
+/// are stored verbatim under a `place_id:` prefix.
+fn entry_key(source: GeocodeMatch, query: &str) -> String {
+    match source {
+        GeocodeMatch::PlaceId => format!("place_id:{}", query),
+        _ => cache_key(query),
+    }
+}
+
+impl GeocodeCache {
+    pub fn load(cache_dir: &Path) -> Self {
+        let path = cache_dir.join(CACHE_FILE);
+        let failures_path = cache_dir.join(FAILURES_FILE);
+        Self {
+            entries: load_json(&path, "geocode cache"),
+            failures: load_json(&failures_path, "geocode failures"),
+            path,
+            failures_path,
+            dirty: false,
+        }
+    }
+
+    pub fn get(&self, provider: &str, source: GeocodeMatch, query: &str) -> Option<&CachedGeocode> {
+        self.entries.get(provider)?.get(&entry_key(source, query))
+    }
+
+    pub fn insert(&mut self, provider: &str, entry: CachedGeocode) {
+        let key = entry_key(entry.match_source, &entry.query);
+        if let Some(failures) = self.failures.get_mut(provider) {
+            failures.remove(&key);
+        }
+        self.entries
+            .entry(provider.to_string())
+            .or_default()
+            .insert(key, entry);
+        self.dirty = true;
+    }
+
+    pub fn failure(
+        &self,
+        provider: &str,
+        source: GeocodeMatch,
+        query: &str,
+    ) -> Option<&FailedGeocode> {
+        self.failures.get(provider)?.get(&entry_key(source, query))
+    }
+
+    /// Remember that `query`, needed by `key`, could not be resolved.
+    pub fn record_failure(
+        &mut self,
+        provider: &str,
+        source: GeocodeMatch,
+        query: &str,
+        key: &str,
+        reason: String,
+    ) {
+        let failure = self
+            .failures
+            .entry(provider.to_string())
+            .or_default()
+            .entry(entry_key(source, query))
+            .or_insert_with(|| FailedGeocode {
+                query: query.to_string(),
+                match_source: source,
+                keys: BTreeSet::new(),
+                reason: String::new(),
+                failed_at: Utc::now(),
+                attempts: 0,
+            });
+        failure.keys.insert(key.to_string());
+        failure.reason = reason;
+        failure.failed_at = Utc::now();
+        failure.attempts += 1;
+        self.dirty = true;
+    }
+
+    /// Every recorded failure, across providers.
+    pub fn failures(&self) -> impl Iterator<Item = &FailedGeocode> {
+        self.failures.values().flat_map(|f| f.values())
+    }
+
+    /// Remove an address from every provider, along with the place details
+    /// cached for the place it resolved to, so a re-geocode can't be answered
+    /// from those. Returns the number of entries removed.
+    pub fn remove(&mut self, address: &str) -> usize {
+        let mut keys = vec![cache_key(address)];
+        for entries in self.entries.values() {
+            if let Some(place_id) = entries.get(&keys[0]).and_then(|e| e.place_id.as_deref()) {
+                keys.push(entry_key(GeocodeMatch::PlaceId, place_id));
+            }
+        }
+        let mut removed = 0;
+        for key in &keys {
+            for entries in self.entries.values_mut() {
+                if entries.remove(key).is_some() {
+                    removed += 1;
+                }
+            }
+            for failures in self.failures.values_mut() {
+                if failures.remove(key).is_some() {
+                    removed += 1;
+                }
+            }
+        }
+        self.dirty |= removed > 0;
+        removed
+    }
+
+    pub fn save(&mut self) -> AnyhowResult<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let json = serde_json::to_string_pretty(&self.entries)?;
+        fsio::write_atomic(&self.path, json)?;
+        let json = serde_json::to_string_pretty(&self.failures)?;
+        fsio::write_atomic(&self.failures_path, json)?;
+        self.dirty = false;
+        info!("Saved geocode cache: {}", self.path.display());
+        Ok(())
+    }
+}
+
+/// Handle the `geocode-cache` subcommand.
+pub fn run_command(cache_dir: &Path, action: &CacheAction) -> AnyhowResult<()> {
+    let mut cache = GeocodeCache::load(cache_dir);
+    match action {
+        CacheAction::Stats => {
+            for (provider, entries) in &cache.entries {
+                let oldest = entries.values().map(|e| e.fetched_at).min();
+                let newest = entries.values().map(|e| e.fetched_at).max();
+                println!(
+                    "{}: {} entries (oldest {}, newest {})",
+                    provider,
+                    entries.len(),
+                    oldest.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                    newest.map(|t| t.to_rfc3339()).unwrap_or_default(),
+                );
+            }
+        }
+        CacheAction::List { contains } => {
+            let needle = contains.as_deref().map(cache_key);
+            for (provider, entries) in &cache.entries {
+                for (key, entry) in entries {
+                    if needle.as_ref().is_some_and(|n| !key.contains(n.as_str())) {
+                        continue;
+                    }
+                    println!(
+                        "[{}] {} -> ({}, {}) {}",
+                        provider,
+                        entry.query,
+                        entry.lat,
+                        entry.lng,
+                        entry.location_type.as_deref().unwrap_or("")
+                    );
+                }
+            }
+        }
+        CacheAction::Show { address } => {
+            let key = cache_key(address);
+            let found: BTreeMap<&String, &CachedGeocode> = cache
+                .entries
+                .iter()
+                .filter_map(|(provider, entries)| entries.get(&key).map(|e| (provider, e)))
+                .collect();
+            anyhow::ensure!(!found.is_empty(), "No cached geocode for '{}'", address);
+            println!("{}", serde_json::to_string_pretty(&found)?);
+        }
+        CacheAction::Failures => {
+            for failure in cache.failures() {
+                println!(
+                    "{} [{}] {} (failed {}x, last {})",
+                    failure.query,
+                    failure.keys.iter().cloned().collect::<Vec<_>>().join(", "),
+                    failure.reason,
+                    failure.attempts,
+                    failure.failed_at.to_rfc3339(),
+                );
+            }
+        }
+        CacheAction::Remove { address } => {
+            let removed = cache.remove(address);
+            anyhow::ensure!(removed > 0, "No cached geocode for '{}'", address);
+            cache.save()?;
+            println!("Removed {} cached result(s) for '{}'", removed, address);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(source: GeocodeMatch, query: &str, place_id: Option<&str>) -> CachedGeocode {
+        CachedGeocode {
+            query: query.to_string(),
+            match_source: source,
+            lat: 37.25,
+            lng: -121.96,
+            formatted_address: None,
+            place_id: place_id.map(str::to_string),
+            location_type: None,
+            partial_match: false,
+            fetched_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn remove_drops_the_place_details_too() {
+        let mut cache = GeocodeCache::load(Path::new("/nonexistent"));
+        let address = "1 Main St, San Jose, CA";
+        cache.insert(
+            "google",
+            entry(GeocodeMatch::Address, address, Some("ChIJabc")),
+        );
+        cache.insert(
+            "google",
+            entry(GeocodeMatch::PlaceId, "ChIJabc", Some("ChIJabc")),
+        );
+        cache.insert(
+            "google",
+            entry(GeocodeMatch::PlaceId, "ChIJxyz", Some("ChIJxyz")),
+        );
+
+        assert_eq!(cache.remove("1 MAIN ST., San Jose, CA"), 2);
+        assert!(
+            cache
+                .get("google", GeocodeMatch::Address, address)
+                .is_none()
+        );
+        assert!(
+            cache
+                .get("google", GeocodeMatch::PlaceId, "ChIJabc")
+                .is_none()
+        );
+        assert!(
+            cache
+                .get("google", GeocodeMatch::PlaceId, "ChIJxyz")
+                .is_some()
+        );
+        assert_eq!(cache.remove(address), 0);
+    }
+}
@@ -0,0 +1,372 @@
+//! Parsing of hand-entered coordinates.
+//!
+//! Accepts the formats people paste from maps and spreadsheets:
+//!
+//! - decimal: `37.5058, -122.2894` or `37.5058 -122.2894`
+//! - hemisphere letters: `37.5058N 122.2894W`
+//! - degrees/minutes/seconds: `37°30'21.1"N 122°17'22.0"W`, `37 30 21.1 N 122 17 22 W`
+//! - decimal commas: `37,5058; -122,2894` or `37,5058 -122,2894`
+//! - stray trailing commas, as copied from JSON: `37.5058, -122.2894,`
+//!
+//! `parse_location` also takes what contributors paste into issues: Google
+//! Maps links, `geo:` URIs and Plus Codes. Everything is parsed offline.
+
+use anyhow::{Context, Result as AnyhowResult};
+use regex::Regex;
+
+use crate::geocoder::plus_code;
+
+/// Parse a location from coordinates, a Google Maps URL, a `geo:` URI or a
+/// Plus Code. Short Plus Codes (`CWC8+R9 Mountain View, CA`) need their
+/// locality resolved to a reference point, which `locate` does.
+pub fn parse_location(
+    input: &str,
+    locate: impl FnOnce(&str) -> AnyhowResult<(f64, f64)>,
+) -> AnyhowResult<(f64, f64)> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+    let (lat, lng) = if lower.starts_with("geo:") {
+        parse_geo_uri(input)?
+    } else if lower.starts_with("http://") || lower.starts_with("https://") {
+        parse_maps_url(input)?
+    } else if let Some((code, rest)) = split_plus_code(input) {
+        if plus_code::is_short(code) {
+            anyhow::ensure!(
+                !rest.is_empty(),
+                "Short Plus Code '{}' needs a locality, e.g. '{} San Jose, CA'",
+                code,
+                code
+            );
+            plus_code::decode_short(code, locate(rest)?)?
+        } else {
+            plus_code::decode(code)?
+        }
+    } else {
+        return parse_coordinates(input);
+    };
+    validate(lat, lng)?;
+    Ok((lat, lng))
+}
+
+/// Split a leading Plus Code from the rest of the text (the locality).
+fn split_plus_code(input: &str) -> Option<(&str, &str)> {
+    let (code, rest) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    let code = code.trim_end_matches(',');
+    plus_code::is_plus_code(code).then(|| (code, rest.trim().trim_start_matches(',').trim()))
+}
+
+/// `geo:37.78,-122.39;u=35`, or `geo:0,0?q=37.78,-122.39(Label)`.
+fn parse_geo_uri(uri: &str) -> AnyhowResult<(f64, f64)> {
+    let body = &uri[4..];
+    let (path, query) = body.split_once('?').unwrap_or((body, ""));
+    let coords = path.split(';').next().unwrap_or_default();
+    let mut parts = coords.split(',').map(|p| p.trim().parse::<f64>());
+    let (Some(Ok(lat)), Some(Ok(lng))) = (parts.next(), parts.next()) else {
+        anyhow::bail!("Invalid geo: URI '{}'", uri);
+    };
+    if lat == 0.0 && lng == 0.0 {
+        let q = query_param(query, &["q"]).map(|q| q.split('(').next().unwrap_or("").to_string());
+        if let Some(q) = q.filter(|q| !q.trim().is_empty()) {
+            return parse_coordinates(&q);
+        }
+    }
+    Ok((lat, lng))
+}
+
+/// First value of any of `names` in a URL query string, percent-decoded.
+fn query_param(query: &str, names: &[&str]) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        names.contains(&name).then(|| {
+            urlencoding::decode(&value.replace('+', " "))
+                .map(|v| v.into_owned())
+                .ok()
+        })?
+    })
+}
+
+/// Coordinates from a Google Maps URL. The `!3d..!4d..` data (the place
+/// itself) wins over a `q=`/`ll=` query, which wins over `@lat,lng` (the
+/// map viewport).
+fn parse_maps_url(url: &str) -> AnyhowResult<(f64, f64)> {
+    let lower = url.to_lowercase();
+    anyhow::ensure!(
+        !lower.contains("goo.gl/") && !lower.contains("g.co/"),
+        "Short links can't be resolved offline; open '{}' in a browser and paste the full URL",
+        url
+    );
+    let decoded = urlencoding::decode(url)
+        .map(|u| u.into_owned())
+        .unwrap_or_else(|_| url.to_string());
+    let number = r"(-?\d+(?:\.\d+)?)";
+
+    let place = Regex::new(&format!(r"!3d{}!4d{}", number, number)).unwrap();
+    if let Some(caps) = place.captures_iter(&decoded).last() {
+        return Ok((caps[1].parse()?, caps[2].parse()?));
+    }
+    if let Some((_, query)) = url.split_once('?') {
+        let names = ["q", "query", "ll", "destination", "center"];
+        if let Some(value) = query_param(query, &names)
+            && let Ok(loc) = parse_location(&value, |_| anyhow::bail!("no reference"))
+        {
+            return Ok(loc);
+        }
+    }
+    let viewport = Regex::new(&format!(r"@{},{}", number, number)).unwrap();
+    if let Some(caps) = viewport.captures(&decoded) {
+        return Ok((caps[1].parse()?, caps[2].parse()?));
+    }
+    anyhow::bail!("No coordinates found in URL '{}'", url)
+}
+
+/// Parse a latitude/longitude pair from free-form text.
+pub fn parse_coordinates(input: &str) -> AnyhowResult<(f64, f64)> {
+    let text = normalize_symbols(input);
+    let (first, second) = split_pair(&text)
+        .with_context(|| format!("Expected a latitude and longitude: '{}'", input))?;
+    let (a, a_hemi) = parse_component(&first)
+        .with_context(|| format!("Invalid coordinate '{}'", first.trim()))?;
+    let (b, b_hemi) = parse_component(&second)
+        .with_context(|| format!("Invalid coordinate '{}'", second.trim()))?;
+
+    // Hemisphere letters may put longitude first.
+    let (lat, lng) = match (a_hemi, b_hemi) {
+        (Some('E' | 'W'), _) | (_, Some('N' | 'S')) => (b, a),
+        _ => (a, b),
+    };
+    for (hemi, want) in [(a_hemi, b_hemi), (b_hemi, a_hemi)] {
+        if let (Some(x), Some(y)) = (hemi, want) {
+            anyhow::ensure!(
+                is_lat_hemi(x) != is_lat_hemi(y),
+                "Both coordinates are {}: '{}'",
+                if is_lat_hemi(x) {
+                    "latitudes"
+                } else {
+                    "longitudes"
+                },
+                input
+            );
+        }
+    }
+    validate(lat, lng)?;
+    Ok((lat, lng))
+}
+
+/// Check that a coordinate pair is on the globe.
+pub fn validate(lat: f64, lng: f64) -> AnyhowResult<()> {
+    anyhow::ensure!(
+        lat.is_finite() && (-90.0..=90.0).contains(&lat),
+        "Latitude {} is out of range",
+        lat
+    );
+    anyhow::ensure!(
+        lng.is_finite() && (-180.0..=180.0).contains(&lng),
+        "Longitude {} is out of range",
+        lng
+    );
+    Ok(())
+}
+
+fn is_lat_hemi(c: char) -> bool {
+    matches!(c, 'N' | 'S')
+}
+
+/// Uppercase and fold the many quote and degree look-alikes to ASCII.
+fn normalize_symbols(input: &str) -> String {
+    input
+        .trim()
+        .to_uppercase()
+        .chars()
+        .map(|c| match c {
+            'º' | '˚' => '°',
+            '′' | '‘' | '’' | '`' | '´' => '\'',
+            '″' | '“' | '”' => '"',
+            '−' | '–' => '-',
+            _ => c,
+        })
+        .collect::<String>()
+        .replace("''", "\"")
+}
+
+/// Split text into its two coordinate halves.
+fn split_pair(text: &str) -> Option<(String, String)> {
+    let text = text.trim().trim_end_matches(',').trim();
+    if let Some((a, b)) = text.split_once(';') {
+        return Some((a.to_string(), b.to_string()));
+    }
+
+    // Hemisphere letters: split after a trailing N/S (`37N 122W`), or before
+    // the E/W of a leading-letter form (`N37 W122`).
+    let letters: Vec<(usize, char)> = text
+        .char_indices()
+        .filter(|(_, c)| matches!(c, 'N' | 'S' | 'E' | 'W'))
+        .collect();
+    if letters.len() == 2 {
+        let (first_pos, first) = letters[0];
+        let (second_pos, _) = letters[1];
+        let leading = text[..first_pos].trim().is_empty();
+        let at = if leading {
+            second_pos
+        } else {
+            first_pos + first.len_utf8()
+        };
+        return Some((text[..at].to_string(), text[at..].to_string()));
+    }
+
+    // A single comma, or a comma followed by a space, separates the halves.
+    if text.matches(',').count() == 1 || text.contains(", ") {
+        let (a, b) = text.split_once(", ").or_else(|| text.split_once(','))?;
+        return Some((a.to_string(), b.to_string()));
+    }
+
+    // Otherwise split whitespace-separated numbers evenly (decimal, or DMS
+    // written with spaces).
+    let tokens: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || matches!(c, '°' | '\'' | '"'))
+        .filter(|t| !t.is_empty())
+        .collect();
+    if tokens.len().is_multiple_of(2) && (2..=6).contains(&tokens.len()) {
+        let half = tokens.len() / 2;
+        return Some((tokens[..half].join(" "), tokens[half..].join(" ")));
+    }
+    None
+}
+
+/// Parse one coordinate in decimal or DMS form, returning the signed value
+/// and its hemisphere letter, if any.
+fn parse_component(text: &str) -> AnyhowResult<(f64, Option<char>)> {
+    let text = text.trim().trim_matches(',').trim();
+    let hemi = text.chars().find(|c| matches!(c, 'N' | 'S' | 'E' | 'W'));
+    let negative = text.starts_with('-') || matches!(hemi, Some('S' | 'W'));
+    anyhow::ensure!(
+        !(text.starts_with('-') && hemi.is_some()),
+        "use either a minus sign or a hemisphere letter"
+    );
+
+    // A comma inside a number is a decimal comma. Degrees, minutes and
+    // seconds must use points, and a number can't have both.
+    let unsigned = text.trim_start_matches(['-', '+']);
+    let number = Regex::new(r"\d+(?:[.,]\d+)*").unwrap();
+    let numbers: Vec<&str> = number.find_iter(unsigned).map(|m| m.as_str()).collect();
+    let has_comma = numbers.iter().any(|s| s.contains(','));
+    anyhow::ensure!(
+        !has_comma || numbers.len() == 1,
+        "decimal commas can't be used with minutes and seconds"
+    );
+    let parts: Vec<f64> = numbers
+        .iter()
+        .map(|s| {
+            anyhow::ensure!(
+                s.matches([',', '.']).count() <= 1,
+                "'{}' is not a single number",
+                s
+            );
+            Ok(s.replace(',', ".").parse::<f64>()?)
+        })
+        .collect::<AnyhowResult<_>>()?;
+    anyhow::ensure!(
+        (1..=3).contains(&parts.len()),
+        "expected degrees, optionally with minutes and seconds"
+    );
+    for &part in &parts[1..] {
+        anyhow::ensure!(part < 60.0, "minutes and seconds must be below 60");
+    }
+    let value = parts
+        .iter()
+        .zip([1.0, 60.0, 3600.0])
+        .map(|(part, div)| part / div)
+        .sum::<f64>();
+    Ok((if negative { -value } else { value }, hemi))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near((lat, lng): (f64, f64), (want_lat, want_lng): (f64, f64)) {
+        assert!(
+            (lat - want_lat).abs() < 1e-4 && (lng - want_lng).abs() < 1e-4,
+            "got ({}, {}), want ({}, {})",
+            lat,
+            lng,
+            want_lat,
+            want_lng
+        );
+    }
+
+    fn no_reference(_: &str) -> AnyhowResult<(f64, f64)> {
+        anyhow::bail!("no reference")
+    }
+
+    #[test]
+    fn parses_dms() {
+        let want = (37.505861, -122.289444);
+        assert_near(
+            parse_coordinates(r#"37°30'21.1"N 122°17'22.0"W"#).unwrap(),
+            want,
+        );
+        assert_near(parse_coordinates("37 30 21.1 N 122 17 22 W").unwrap(), want);
+        assert_near(
+            parse_coordinates("122°17'22\"W, 37°30'21.1\"N").unwrap(),
+            want,
+        );
+        assert!(parse_coordinates("37°75'N 122°W").is_err());
+    }
+
+    #[test]
+    fn parses_decimal_commas() {
+        let want = (37.5058, -122.2894);
+        assert_near(parse_coordinates("37,5058; -122,2894").unwrap(), want);
+        assert_near(parse_coordinates("37,5058 -122,2894").unwrap(), want);
+        assert_near(parse_coordinates("37.5058, -122.2894,").unwrap(), want);
+        assert!(parse_coordinates("1,234.5, 2").is_err());
+        assert!(parse_coordinates("37°30,5'N 122°17'W").is_err());
+    }
+
+    #[test]
+    fn parses_geo_uris() {
+        let want = (37.78, -122.39);
+        assert_near(
+            parse_location("geo:37.78,-122.39;u=35", no_reference).unwrap(),
+            want,
+        );
+        assert_near(
+            parse_location("geo:0,0?q=37.78,-122.39(Label)", no_reference).unwrap(),
+            want,
+        );
+        assert!(parse_location("geo:north,south", no_reference).is_err());
+    }
+
+    #[test]
+    fn parses_maps_urls() {
+        let place =
+            "https://www.google.com/maps/place/X/@37.1,-122.1,15z/data=!3d37.4219!4d-122.0841";
+        assert_near(
+            parse_location(place, no_reference).unwrap(),
+            (37.4219, -122.0841),
+        );
+        let query = "https://maps.google.com/?q=37.4219,-122.0841";
+        assert_near(
+            parse_location(query, no_reference).unwrap(),
+            (37.4219, -122.0841),
+        );
+        let viewport = "https://www.google.com/maps/@37.4219,-122.0841,15z";
+        assert_near(
+            parse_location(viewport, no_reference).unwrap(),
+            (37.4219, -122.0841),
+        );
+        assert!(parse_location("https://goo.gl/maps/abc", no_reference).is_err());
+    }
+
+    #[test]
+    fn parses_plus_codes() {
+        let want = (37.4220625, -122.0840625);
+        assert_near(parse_location("849VCWC8+R9", no_reference).unwrap(), want);
+        let short = parse_location("CWC8+R9 Mountain View, CA", |locality| {
+            assert_eq!(locality, "Mountain View, CA");
+            Ok((37.39, -122.08))
+        });
+        assert_near(short.unwrap(), want);
+        assert!(parse_location("CWC8+R9", no_reference).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Third-party location override sources, e.g. firstmap's
+//! `custom_locations.json`, merged in during location resolution.
+//!
+//! Sources are local files or URLs, listed in priority order in the
+//! `[external]` section of `frcmap.toml`. A URL is fetched through the cached
+//! HTTP client and the last good copy is kept in `<cache>/external/`, so a
+//! run still works when the source is unreachable.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result as AnyhowResult};
+use log::{error, info, warn};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Serialize;
+
+use crate::{
+    config::{ExternalSettings, ExternalSourceSettings, LocationKind},
+    fsio, geo,
+    geocoder::{
+        override_formats::{self, Format},
+        overrides::normalize_key,
+        types::{LocationDict, LocationOverride},
+    },
+};
+
+/// Name the manual override file goes by in conflict reports.
+const OVERRIDE_SOURCE_NAME: &str = "overrides";
+
+/// Entries from one third-party source.
+#[derive(Debug, Clone)]
+pub struct ExternalSource {
+    pub name: String,
+    pub entries: LocationDict,
+}
+
+/// External sources for teams and events, each in priority order.
+#[derive(Debug, Clone, Default)]
+pub struct ExternalSources {
+    pub teams: Vec<ExternalSource>,
+    pub events: Vec<ExternalSource>,
+}
+
+/// One source's location for a key involved in a conflict.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConflictingLocation {
+    pub source: String,
+    pub lat: f64,
+    pub lng: f64,
+}
+
+/// A key whose location differs between sources by more than the
+/// configured radius. `locations` are in priority order, so the first one
+/// is the location that wins.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExternalConflict {
+    pub key: String,
+    pub distance_m: f64,
+    pub locations: Vec<ConflictingLocation>,
+}
+
+/// Load every configured source. A source that can't be read is skipped
+/// with an error rather than failing the run.
+pub async fn load_sources(
+    client: &ClientWithMiddleware,
+    settings: &ExternalSettings,
+    cache_path: &Path,
+) -> ExternalSources {
+    let mut sources = ExternalSources::default();
+    for source in &settings.sources {
+        let entries = match read_source(client, source, cache_path).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Failed to load external source {}: {:#}", source.name, e);
+                continue;
+            }
+        };
+        info!(
+            "Loaded {} locations from external source {}",
+            entries.len(),
+            source.name
+        );
+        let loaded = ExternalSource {
+            name: source.name.clone(),
+            entries,
+        };
+        match source.kind {
+            LocationKind::Teams => sources.teams.push(loaded),
+            LocationKind::Events => sources.events.push(loaded),
+        }
+    }
+    sources
+}
+
+/// Where the last good copy of a URL source is kept. Anything but ASCII
+/// letters, digits, `_` and `-` in the source name becomes `_`, so a name
+/// can't point outside the cache.
+fn cached_copy(cache_path: &Path, source: &ExternalSourceSettings, format: Format) -> PathBuf {
+    let ext = match format {
+        Format::Json => "json",
+        Format::Toml => "toml",
+        Format::Yaml => "yaml",
+    };
+    let name: String = source
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '-') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    cache_path
+        .join("external")
+        .join(format!("{}.{}", name, ext))
+}
+
+async fn fetch(client: &ClientWithMiddleware, url: &str) -> AnyhowResult<String> {
+    Ok(client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?)
+}
+
+async fn read_source(
+    client: &ClientWithMiddleware,
+    source: &ExternalSourceSettings,
+    cache_path: &Path,
+) -> AnyhowResult<LocationDict> {
+    let (format, content) = match (&source.path, &source.url) {
+        (Some(path), _) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            (Format::of(path), content)
+        }
+        (None, Some(url)) => {
+            let format = Format::of(Path::new(url.split(['?', '#']).next().unwrap_or(url)));
+            let copy = cached_copy(cache_path, source, format);
+            match fetch(client, url).await {
+                Ok(content) => {
+                    std::fs::create_dir_all(copy.parent().unwrap())?;
+                    fsio::write_atomic(&copy, &content)?;
+                    (format, content)
+                }
+                Err(e) if copy.exists() => {
+                    warn!(
+                        "Failed to fetch {} ({:#}), using the copy from {}",
+                        url,
+                        e,
+                        copy.display()
+                    );
+                    (format, std::fs::read_to_string(&copy)?)
+                }
+                Err(e) => return Err(e.context(format!("Failed to fetch {}", url))),
+            }
+        }
+        (None, None) => anyhow::bail!("needs a `path` or a `url`"),
+    };
+
+    let mut entries = LocationDict::new();
+    for (raw_key, value) in override_formats::parse_entries(format, &content)? {
+        if raw_key == override_formats::COMMENT_KEY {
+            continue;
+        }
+        let key = normalize_key(&raw_key, source.kind);
+        match serde_json::from_value::<LocationOverride>(value) {
+            Ok(entry) => {
+                entries.insert(key, entry);
+            }
+            Err(e) => warn!("Skipping {} from {}: {}", raw_key, source.name, e),
+        }
+    }
+    Ok(entries)
+}
+
+/// Keys in `keys` whose applicable locations in the override file and the
+/// external sources are more than `radius_m` apart, sorted by key.
+pub fn find_conflicts<'a>(
+    keys: impl IntoIterator<Item = &'a String>,
+    overrides: &LocationDict,
+    sources: &[ExternalSource],
+    year: u32,
+    radius_m: f64,
+) -> Vec<ExternalConflict> {
+    let mut conflicts = Vec::new();
+    for key in keys {
+        let candidates = std::iter::once((OVERRIDE_SOURCE_NAME, overrides.get(key))).chain(
+            sources
+                .iter()
+                .map(|s| (s.name.as_str(), s.entries.get(key))),
+        );
+        let locations: Vec<ConflictingLocation> = candidates
+            .filter_map(|(name, entry)| {
+                let entry = entry.filter(|e| e.applies_to(year))?;
+                Some(ConflictingLocation {
+                    source: name.to_string(),
+                    lat: entry.lat?,
+                    lng: entry.lng?,
+                })
+            })
+            .collect();
+        let distance_m = locations
+            .iter()
+            .flat_map(|a| {
+                locations
+                    .iter()
+                    .map(move |b| geo::distance_m(a.lat, a.lng, b.lat, b.lng))
+            })
+            .fold(0.0, f64::max);
+        if distance_m > radius_m {
+            conflicts.push(ExternalConflict {
+                key: key.clone(),
+                distance_m: distance_m.round(),
+                locations,
+            });
+        }
+    }
+    conflicts.sort_by(|a, b| a.key.cmp(&b.key));
+    conflicts
+}
@@ -0,0 +1,473 @@
+//! Offline postal-code and city centroids from GeoNames dumps.
+//!
+//! Tab-separated files are read from the gazetteer directory, all in
+//! the formats published at <https://download.geonames.org/export/>:
+//!
+//! - `postal_codes.txt`: the postal code dump (`zip/allCountries.txt`)
+//! - `cities.txt`: a city dump (e.g. `dump/cities500.txt`)
+//! - `admin1_codes.txt`: state/province names (`dump/admin1CodesASCII.txt`),
+//!   so cities can be matched against TBA's spelled-out `state_prov`
+//!
+//! Any file may be missing; lookups then only use what is there.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result as AnyhowResult};
+use log::{info, warn};
+use reqwest_middleware::ClientWithMiddleware;
+use serde::Serialize;
+
+use crate::{config::GazetteerSettings, fsio, geo, geocoder::normalize::fold};
+
+const POSTAL_FILE: &str = "postal_codes.txt";
+const CITIES_FILE: &str = "cities.txt";
+const ADMIN1_FILE: &str = "admin1_codes.txt";
+
+/// Countries TBA lists teams in: the ISO 3166-1 alpha-2 code GeoNames uses,
+/// TBA's spelling of the name, and other spellings (folded) that mean the same.
+const COUNTRIES: &[(&str, &str, &[&str])] = &[
+    ("US", "USA", &["united states"]),
+    ("CA", "Canada", &[]),
+    ("MX", "Mexico", &[]),
+    ("TR", "Türkiye", &["turkey"]),
+    ("BR", "Brazil", &[]),
+    ("CN", "China", &[]),
+    ("AU", "Australia", &[]),
+    ("TW", "Chinese Taipei", &["taiwan"]),
+    ("IN", "India", &[]),
+    ("DO", "Dominican Republic", &[]),
+    ("PL", "Poland", &[]),
+    ("PA", "Panama", &[]),
+    ("JP", "Japan", &[]),
+    ("CO", "Colombia", &[]),
+    ("KR", "South Korea", &[]),
+    ("NL", "Netherlands", &[]),
+    ("IL", "Israel", &[]),
+    ("CZ", "Czech Republic", &["czechia"]),
+    ("VN", "Vietnam", &[]),
+    ("GB", "United Kingdom", &[]),
+    ("SE", "Sweden", &[]),
+    ("ZA", "South Africa", &[]),
+    ("RO", "Romania", &[]),
+    ("PH", "Philippines", &[]),
+    ("MY", "Malaysia", &[]),
+    ("FR", "France", &[]),
+    ("HR", "Croatia", &[]),
+    ("BG", "Bulgaria", &[]),
+    ("BZ", "Belize", &[]),
+    ("DE", "Germany", &[]),
+    ("CH", "Switzerland", &[]),
+    ("CL", "Chile", &[]),
+    ("EC", "Ecuador", &[]),
+    ("PY", "Paraguay", &[]),
+    ("KZ", "Kazakhstan", &[]),
+    ("AZ", "Azerbaijan", &[]),
+    ("LY", "Libya", &[]),
+    ("ET", "Ethiopia", &[]),
+];
+
+/// TBA country name for an ISO country code.
+pub fn country_name(code: &str) -> Option<&'static str> {
+    COUNTRIES
+        .iter()
+        .find(|(c, _, _)| *c == code)
+        .map(|(_, name, _)| *name)
+}
+
+/// ISO country code for a TBA country name, or another spelling of it.
+pub fn country_code(country: &str) -> Option<&'static str> {
+    let folded = fold(country);
+    COUNTRIES
+        .iter()
+        .find(|(_, name, aliases)| fold(name) == folded || aliases.contains(&folded.as_str()))
+        .map(|(code, _, _)| *code)
+}
+
+/// The locality fields a gazetteer lookup works from.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Locality<'a> {
+    pub postal_code: Option<&'a str>,
+    pub city: Option<&'a str>,
+    pub state_prov: Option<&'a str>,
+    pub country: Option<&'a str>,
+}
+
+/// How precise a gazetteer hit is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GazetteerPrecision {
+    PostalCode,
+    City,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GazetteerMatch {
+    pub lat: f64,
+    pub lng: f64,
+    pub precision: GazetteerPrecision,
+}
+
+/// A city near a point, from [`Gazetteer::places_near`].
+#[derive(Debug, Clone)]
+pub struct NearbyPlace {
+    pub name: String,
+    /// State or province name, when the admin1 file has it.
+    pub admin1: Option<String>,
+    pub country: String,
+    pub distance_m: f64,
+}
+
+/// A city in the reverse-lookup grid.
+#[derive(Debug, Clone)]
+struct Place {
+    name: String,
+    admin1: Option<String>,
+    country: String,
+    lat: f64,
+    lng: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Centroid {
+    lat: f64,
+    lng: f64,
+    /// Population for cities, number of merged rows for postal codes.
+    weight: u64,
+}
+
+/// In-memory index of the gazetteer files.
+#[derive(Default)]
+pub struct Gazetteer {
+    /// (country code, postal code) -> centroid
+    postal: HashMap<(String, String), Centroid>,
+    /// (country code, folded admin1 name or code, folded city) -> most populous match
+    cities: HashMap<(String, String, String), Centroid>,
+    /// (country code, folded city) -> most populous match in any admin1
+    cities_any_state: HashMap<(String, String), Centroid>,
+    /// Every city, bucketed by whole degree of latitude and longitude.
+    grid: HashMap<(i32, i32), Vec<Place>>,
+}
+
+/// Grid cell holding a point.
+fn grid_cell(lat: f64, lng: f64) -> (i32, i32) {
+    (lat.floor() as i32, lng.floor() as i32)
+}
+
+/// Postal codes are compared without spaces and in upper case.
+fn postal_key(code: &str) -> String {
+    code.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+impl Gazetteer {
+    /// Load whichever gazetteer files exist in `dir`.
+    pub fn load(dir: &Path) -> Self {
+        let mut gazetteer = Self::default();
+
+        let postal_path = dir.join(POSTAL_FILE);
+        if postal_path.is_file() {
+            match std::fs::read_to_string(&postal_path) {
+                Ok(content) => gazetteer.read_postal_codes(&content),
+                Err(e) => warn!("Failed to read {}: {}", postal_path.display(), e),
+            }
+        }
+        let admin1_path = dir.join(ADMIN1_FILE);
+        let admin1 = match std::fs::read_to_string(&admin1_path) {
+            Ok(content) => read_admin1_names(&content),
+            Err(_) => HashMap::new(),
+        };
+        let cities_path = dir.join(CITIES_FILE);
+        if cities_path.is_file() {
+            match std::fs::read_to_string(&cities_path) {
+                Ok(content) => gazetteer.read_cities(&content, &admin1),
+                Err(e) => warn!("Failed to read {}: {}", cities_path.display(), e),
+            }
+        }
+
+        if gazetteer.is_empty() {
+            info!("No gazetteer data in {}", dir.display());
+        } else {
+            info!(
+                "Loaded gazetteer: {} postal codes, {} cities",
+                gazetteer.postal.len(),
+                gazetteer.cities.len()
+            );
+        }
+        gazetteer
+    }
+
+    /// A gazetteer from the contents of a postal code and a city file.
+    #[cfg(test)]
+    pub(crate) fn from_text(postal_codes: &str, cities: &str) -> Self {
+        let mut gazetteer = Self::default();
+        gazetteer.read_postal_codes(postal_codes);
+        gazetteer.read_cities(cities, &HashMap::new());
+        gazetteer
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.postal.is_empty() && self.cities.is_empty()
+    }
+
+    /// GeoNames postal format: country, postal code, place, admin1 name,
+    /// admin1 code, admin2 name, admin2 code, admin3 name, admin3 code, lat, lng, accuracy.
+    /// Codes shared by several places are averaged.
+    fn read_postal_codes(&mut self, content: &str) {
+        for line in content.lines() {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 11 {
+                continue;
+            }
+            let (Ok(lat), Ok(lng)) = (cols[9].parse::<f64>(), cols[10].parse::<f64>()) else {
+                continue;
+            };
+            let key = (cols[0].to_string(), postal_key(cols[1]));
+            let entry = self.postal.entry(key).or_insert(Centroid {
+                lat: 0.0,
+                lng: 0.0,
+                weight: 0,
+            });
+            let n = entry.weight as f64;
+            entry.lat = (entry.lat * n + lat) / (n + 1.0);
+            entry.lng = (entry.lng * n + lng) / (n + 1.0);
+            entry.weight += 1;
+        }
+    }
+
+    /// GeoNames city format: id, name, ascii name, alternate names, lat, lng,
+    /// feature class, feature code, country, cc2, admin1 code, ..., population (col 14).
+    /// Each city is indexed under its admin1 code and, when `admin1` knows
+    /// it, the admin1 name.
+    fn read_cities(&mut self, content: &str, admin1: &HashMap<String, String>) {
+        for line in content.lines() {
+            let cols: Vec<&str> = line.split('\t').collect();
+            if cols.len() < 15 {
+                continue;
+            }
+            let (Ok(lat), Ok(lng)) = (cols[4].parse::<f64>(), cols[5].parse::<f64>()) else {
+                continue;
+            };
+            let population = cols[14].parse::<u64>().unwrap_or(0);
+            let centroid = Centroid {
+                lat,
+                lng,
+                weight: population,
+            };
+            let country = cols[8].to_string();
+            let admin1_name = admin1.get(&format!("{}.{}", country, cols[10]));
+            self.grid
+                .entry(grid_cell(lat, lng))
+                .or_default()
+                .push(Place {
+                    name: cols[1].to_string(),
+                    admin1: admin1_name.cloned(),
+                    country: country.clone(),
+                    lat,
+                    lng,
+                });
+            let mut states = vec![fold(cols[10])];
+            if let Some(name) = admin1_name {
+                states.push(fold(name));
+            }
+
+            let mut names = vec![fold(cols[1]), fold(cols[2])];
+            names.dedup();
+            for name in names {
+                for state in &states {
+                    keep_largest(
+                        &mut self.cities,
+                        (country.clone(), state.clone(), name.clone()),
+                        centroid,
+                    );
+                }
+                keep_largest(
+                    &mut self.cities_any_state,
+                    (country.clone(), name),
+                    centroid,
+                );
+            }
+        }
+    }
+
+    /// Find a centroid for a locality: by postal code first, then by city
+    /// within the state, then by city anywhere in the country.
+    pub fn lookup(&self, locality: &Locality) -> Option<GazetteerMatch> {
+        let country = country_code(locality.country?)?;
+
+        if let Some(postal) = locality.postal_code.map(postal_key) {
+            // Try the full code, then the part before a dash (ZIP+4), then
+            // the first three characters (Canadian FSA-only dumps).
+            let mut candidates = vec![postal.clone()];
+            if let Some((head, _)) = postal.split_once('-') {
+                candidates.push(head.to_string());
+            }
+            if country == "CA" && postal.chars().count() > 3 {
+                candidates.push(postal.chars().take(3).collect());
+            }
+            for candidate in candidates {
+                if let Some(c) = self.postal.get(&(country.to_string(), candidate)) {
+                    return Some(GazetteerMatch {
+                        lat: c.lat,
+                        lng: c.lng,
+                        precision: GazetteerPrecision::PostalCode,
+                    });
+                }
+            }
+        }
+
+        let city = fold(locality.city?);
+        let state = locality.state_prov.map(fold).unwrap_or_default();
+        self.cities
+            .get(&(country.to_string(), state, city.clone()))
+            .or_else(|| self.cities_any_state.get(&(country.to_string(), city)))
+            .map(|c| GazetteerMatch {
+                lat: c.lat,
+                lng: c.lng,
+                precision: GazetteerPrecision::City,
+            })
+    }
+
+    /// Look up free text such as `Mountain View, CA` or `Istanbul, Turkey`.
+    /// Text without a recognised country is assumed to be in the USA.
+    pub fn lookup_text(&self, text: &str) -> Option<GazetteerMatch> {
+        let parts: Vec<&str> = text
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect();
+        let (country, rest) = match parts.split_last() {
+            Some((last, rest)) if country_code(last).is_some() => (*last, rest),
+            _ => ("USA", &parts[..]),
+        };
+        self.lookup(&Locality {
+            postal_code: None,
+            city: Some(rest.first()?),
+            state_prov: rest.get(1).copied(),
+            country: Some(country),
+        })
+    }
+
+    /// Cities within `radius_m` of a point, nearest first. Empty without a
+    /// city file.
+    pub fn places_near(&self, lat: f64, lng: f64, radius_m: f64) -> Vec<NearbyPlace> {
+        const DEGREE_M: f64 = 111_000.0;
+        let (row, col) = grid_cell(lat, lng);
+        let rows = (radius_m / DEGREE_M).ceil() as i32;
+        let cols = (radius_m / (DEGREE_M * lat.to_radians().cos().max(0.01)))
+            .ceil()
+            .min(180.0) as i32;
+
+        let mut found = Vec::new();
+        for r in row - rows..=row + rows {
+            for c in col - cols..=col + cols {
+                // Wrap across the antimeridian.
+                let c = (c + 180).rem_euclid(360) - 180;
+                for place in self.grid.get(&(r, c)).into_iter().flatten() {
+                    let distance_m = geo::distance_m(lat, lng, place.lat, place.lng);
+                    if distance_m <= radius_m {
+                        found.push(NearbyPlace {
+                            name: place.name.clone(),
+                            admin1: place.admin1.clone(),
+                            country: place.country.clone(),
+                            distance_m,
+                        });
+                    }
+                }
+            }
+        }
+        found.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+        found
+    }
+}
+
+/// GeoNames admin1 format: `CC.code`, name, ascii name, geoname id.
+fn read_admin1_names(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            Some((cols.next()?.to_string(), cols.next()?.to_string()))
+        })
+        .collect()
+}
+
+fn keep_largest<K: std::hash::Hash + Eq>(map: &mut HashMap<K, Centroid>, key: K, c: Centroid) {
+    map.entry(key)
+        .and_modify(|existing| {
+            if c.weight > existing.weight {
+                *existing = c;
+            }
+        })
+        .or_insert(c);
+}
+
+/// Download any gazetteer file that is missing locally but has a URL
+/// configured. Each file is only ever fetched once. GeoNames also publishes
+/// the dumps zipped; only the plain `.txt` files can be used as they are.
+pub async fn ensure_files(
+    client: &ClientWithMiddleware,
+    settings: &GazetteerSettings,
+) -> AnyhowResult<()> {
+    let downloads = [
+        (POSTAL_FILE, settings.postal_codes_url.as_deref()),
+        (CITIES_FILE, settings.cities_url.as_deref()),
+        (ADMIN1_FILE, settings.admin1_codes_url.as_deref()),
+    ];
+    for (name, url) in downloads {
+        let path: PathBuf = settings.path.join(name);
+        let Some(url) = url else { continue };
+        if path.exists() {
+            continue;
+        }
+        let url_path = url.split(['?', '#']).next().unwrap_or_default();
+        anyhow::ensure!(
+            url_path.ends_with(".txt"),
+            "Gazetteer URL for {} must point at a plain .txt dump, not {}; \
+             unzip it into {} instead",
+            name,
+            url,
+            settings.path.display()
+        );
+        info!("Downloading gazetteer {} from {}", name, url);
+        let body = client
+            .get(url)
+            .send()
+            .await
+            .with_context(|| format!("Gazetteer download failed: {}", url))?
+            .error_for_status()?
+            .bytes()
+            .await?;
+        std::fs::create_dir_all(&settings.path)?;
+        fsio::write_atomic(&path, &body)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_zipped_downloads() {
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build();
+        let settings = GazetteerSettings {
+            path: std::env::temp_dir().join(format!("frcmap-gazetteer-{}", std::process::id())),
+            postal_codes_url: Some(
+                "https://download.geonames.org/export/zip/allCountries.zip".into(),
+            ),
+            ..GazetteerSettings::default()
+        };
+        let err = ensure_files(&client, &settings).await.unwrap_err();
+        assert!(
+            err.to_string().contains("must point at a plain .txt dump"),
+            "{}",
+            err
+        );
+        assert!(!settings.path.exists());
+    }
+}
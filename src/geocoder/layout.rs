@@ -0,0 +1,354 @@
+//! Display layout for objects that share (almost) the same location.
+//!
+//! Points within `group_radius_m` of each other are grouped together, then
+//! spread around the group's centroid so every marker stays visible and
+//! clickable. The spread is stored as a display offset next to the
+//! canonical location, so it never leaks into the archives. Every step is
+//! deterministic: the same input always yields the same output.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    f64::consts::{PI, TAU},
+};
+
+use log::warn;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::Deserialize;
+
+use crate::{
+    config::LayoutSettings,
+    geo,
+    map_types::{DisplayOffset, HasLocation},
+};
+
+/// How the members of a co-located group are arranged.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Arrangement {
+    /// Concentric rings around the centroid.
+    #[default]
+    Ring,
+    /// A Fermat spiral out from the centroid; denser for large groups.
+    Spiral,
+    /// Seeded random noise around the original point.
+    Jitter,
+}
+
+/// Compare keys so that `frc254` sorts before `frc1114`.
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let split = |s: &str| {
+        let digits = s.len() - s.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let (prefix, number) = s.split_at(s.len() - digits);
+        (prefix.to_string(), number.parse::<u64>().unwrap_or(0))
+    };
+    split(a).cmp(&split(b)).then_with(|| a.cmp(b))
+}
+
+/// Group located points that lie within `radius_m` of one another
+/// (single-linkage). Returns groups of two or more indices into `points`,
+/// in a stable order.
+fn group_points(points: &[(f64, f64)], radius_m: f64) -> Vec<Vec<usize>> {
+    let mut parent: Vec<usize> = (0..points.len()).collect();
+    fn find(parent: &mut [usize], i: usize) -> usize {
+        let mut root = i;
+        while parent[root] != root {
+            root = parent[root];
+        }
+        parent[i] = root;
+        root
+    }
+
+    // Bucket points into grid cells about `radius_m` wide so only
+    // neighbouring cells need to be compared.
+    let cell_deg = (radius_m.max(0.01) / 111_320.0).max(1e-9);
+    let cell = |(lat, lng): (f64, f64)| {
+        (
+            (lat / cell_deg).floor() as i64,
+            (lng / cell_deg).floor() as i64,
+        )
+    };
+    let mut grid: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (i, p) in points.iter().enumerate() {
+        grid.entry(cell(*p)).or_default().push(i);
+    }
+
+    for (i, &(lat, lng)) in points.iter().enumerate() {
+        let (cy, cx) = cell((lat, lng));
+        // Longitude cells shrink towards the poles, so widen the search there.
+        let span = (1.0 / lat.to_radians().cos().max(0.01)).ceil() as i64;
+        for dy in -1..=1 {
+            for dx in -span..=span {
+                let Some(others) = grid.get(&(cy + dy, cx + dx)) else {
+                    continue;
+                };
+                for &j in others {
+                    if j <= i {
+                        continue;
+                    }
+                    let (olat, olng) = points[j];
+                    let same = lat.to_bits() == olat.to_bits() && lng.to_bits() == olng.to_bits();
+                    if same || geo::distance_m(lat, lng, olat, olng) <= radius_m {
+                        let (ri, rj) = (find(&mut parent, i), find(&mut parent, j));
+                        if ri != rj {
+                            parent[ri.max(rj)] = ri.min(rj);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..points.len() {
+        let root = find(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    groups.sort();
+    groups
+}
+
+/// Offsets (east, north) in meters for `n` members on concentric rings.
+/// Ring k holds 6k members, which keeps neighbours roughly `spacing_m` apart.
+fn ring_offsets(n: usize, spacing_m: f64) -> Vec<(f64, f64)> {
+    let mut offsets = Vec::with_capacity(n);
+    let mut ring = 1;
+    while offsets.len() < n {
+        let count = (6 * ring).min(n - offsets.len());
+        let radius = spacing_m * ring as f64;
+        for i in 0..count {
+            let angle = PI / 2.0 - TAU * i as f64 / count as f64;
+            offsets.push((radius * angle.cos(), radius * angle.sin()));
+        }
+        ring += 1;
+    }
+    offsets
+}
+
+/// Offsets (east, north) in meters for `n` members on a Fermat spiral.
+fn spiral_offsets(n: usize, spacing_m: f64) -> Vec<(f64, f64)> {
+    let golden_angle = PI * (3.0 - 5f64.sqrt());
+    (0..n)
+        .map(|i| {
+            let radius = spacing_m * (i as f64 + 1.0).sqrt();
+            let angle = PI / 2.0 - golden_angle * i as f64;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+/// Deterministic RNG for jittering `key`: FNV-1a over the seed and key feeds
+/// a ChaCha stream, both of which are stable across platforms and releases.
+fn jitter_rng(seed: u64, key: &str) -> ChaCha8Rng {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in seed.to_le_bytes().iter().chain(key.as_bytes()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    ChaCha8Rng::seed_from_u64(hash)
+}
+
+/// Seeded Normal(0, 0.001°) offset for `key`, as (dlat, dlng), pulled back to
+/// at most `radius_m`. The cap is applied in latitude degrees, which never
+/// undershoots the distance east.
+fn jitter_offset(seed: u64, key: &str, radius_m: f64) -> (f64, f64) {
+    use rand_distr::{Distribution, Normal};
+    let normal = Normal::new(0.0, 0.001).unwrap();
+    let mut rng = jitter_rng(seed, key);
+    let (dlat, dlng): (f64, f64) = (normal.sample(&mut rng), normal.sample(&mut rng));
+    let max = geo::offset_m(0.0, 0.0, 0.0, radius_m).0;
+    let scale = (max / dlat.hypot(dlng)).min(1.0);
+    (dlat * scale, dlng * scale)
+}
+
+/// Spread co-located objects according to `settings` and record, on each
+/// member, the keys it shares its location with. Only display offsets are
+/// set; canonical locations are left untouched. Ignored objects aren't shown,
+/// so they take no part.
+pub fn apply<T: HasLocation>(
+    objects: &mut HashMap<String, T>,
+    settings: &LayoutSettings,
+    obj_type: &str,
+) {
+    let mut keys: Vec<String> = objects
+        .iter()
+        .filter(|(_, o)| o.has_location() && !o.is_ignored())
+        .map(|(k, _)| k.clone())
+        .collect();
+    keys.sort_by(|a, b| natural_cmp(a, b));
+    let points: Vec<(f64, f64)> = keys
+        .iter()
+        .map(|k| (objects[k].lat().unwrap(), objects[k].lng().unwrap()))
+        .collect();
+
+    for group in group_points(&points, settings.group_radius_m) {
+        let members: Vec<&String> = group.iter().map(|&i| &keys[i]).collect();
+        warn!(
+            "{} locations overlap, spreading them out: {}",
+            obj_type,
+            members
+                .iter()
+                .map(|k| k.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let n = group.len() as f64;
+        let lat_c = group.iter().map(|&i| points[i].0).sum::<f64>() / n;
+        let lng_c = group.iter().map(|&i| points[i].1).sum::<f64>() / n;
+
+        let positions: Vec<(f64, f64)> = match settings.arrangement {
+            // Jitter keeps the first member in place and nudges the rest,
+            // matching the original behaviour.
+            Arrangement::Jitter => group
+                .iter()
+                .enumerate()
+                .map(|(pos, &i)| {
+                    let (lat, lng) = points[i];
+                    if pos == 0 {
+                        return (lat, lng);
+                    }
+                    let (dlat, dlng) =
+                        jitter_offset(settings.jitter_seed, &keys[i], settings.jitter_radius_m);
+                    (lat + dlat, lng + dlng)
+                })
+                .collect(),
+            Arrangement::Ring | Arrangement::Spiral => {
+                let offsets = if settings.arrangement == Arrangement::Ring {
+                    ring_offsets(group.len(), settings.spacing_m)
+                } else {
+                    spiral_offsets(group.len(), settings.spacing_m)
+                };
+                offsets
+                    .into_iter()
+                    .map(|(east, north)| geo::offset_m(lat_c, lng_c, east, north))
+                    .collect()
+            }
+        };
+
+        for (&i, (lat, lng)) in group.iter().zip(positions) {
+            let key = &keys[i];
+            let others: Vec<String> = members
+                .iter()
+                .filter(|k| **k != key)
+                .map(|k| k.to_string())
+                .collect();
+            let (canon_lat, canon_lng) = points[i];
+            let offset = DisplayOffset {
+                lat: lat - canon_lat,
+                lng: lng - canon_lng,
+            };
+            let obj = objects.get_mut(key).unwrap();
+            obj.set_display_offset((offset.lat != 0.0 || offset.lng != 0.0).then_some(offset));
+            obj.set_colocated_with(others);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{map_types::TeamData, tba::types::TbaTeam};
+
+    fn distance((east, north): (f64, f64)) -> f64 {
+        east.hypot(north)
+    }
+
+    #[test]
+    fn groups_points_within_the_radius() {
+        // 0.0001 degrees of latitude is about 11 m.
+        let points = [
+            (37.0, -122.0),
+            (40.0, -75.0),
+            (37.0001, -122.0),
+            (37.0002, -122.0),
+            (40.0, -75.0),
+            (37.01, -122.0),
+        ];
+        // Single linkage chains the first, third and fourth points together.
+        assert_eq!(group_points(&points, 15.0), [vec![0, 2, 3], vec![1, 4]]);
+        assert_eq!(group_points(&points, 5.0), [vec![1, 4]]);
+        assert!(group_points(&points[..2], 1000.0).is_empty());
+    }
+
+    #[test]
+    fn ring_offsets_fill_rings_of_six_k() {
+        let offsets = ring_offsets(10, 60.0);
+        assert_eq!(offsets.len(), 10);
+        // Six on the first ring, starting due north, and the rest on the second.
+        assert!((offsets[0].0).abs() < 1e-9 && (offsets[0].1 - 60.0).abs() < 1e-9);
+        assert!(
+            offsets[..6]
+                .iter()
+                .all(|&o| (distance(o) - 60.0).abs() < 1e-9)
+        );
+        assert!(
+            offsets[6..]
+                .iter()
+                .all(|&o| (distance(o) - 120.0).abs() < 1e-9)
+        );
+        // The last ring spreads its members evenly, here four at right angles.
+        assert!((offsets[7].0 - 120.0).abs() < 1e-9 && offsets[7].1.abs() < 1e-9);
+    }
+
+    #[test]
+    fn spiral_offsets_grow_with_the_square_root() {
+        let offsets = spiral_offsets(5, 60.0);
+        assert_eq!(offsets.len(), 5);
+        for (i, &offset) in offsets.iter().enumerate() {
+            let want = 60.0 * (i as f64 + 1.0).sqrt();
+            assert!((distance(offset) - want).abs() < 1e-9);
+        }
+        // Consecutive members sit a golden angle apart, so none overlap.
+        assert!(
+            offsets
+                .windows(2)
+                .all(|w| distance((w[0].0 - w[1].0, w[0].1 - w[1].1)) > 30.0)
+        );
+    }
+
+    #[test]
+    fn jitter_is_stable_and_bounded() {
+        assert_eq!(
+            jitter_offset(7, "frc254", 300.0),
+            jitter_offset(7, "frc254", 300.0)
+        );
+        assert_ne!(
+            jitter_offset(7, "frc254", 300.0),
+            jitter_offset(8, "frc254", 300.0)
+        );
+        assert_ne!(
+            jitter_offset(7, "frc254", 300.0),
+            jitter_offset(7, "frc1678", 300.0)
+        );
+
+        // At 60° north a degree of longitude is half as long, so check the
+        // distance in meters; 20 m is well under the usual ~110 m spread.
+        let (lat, lng) = (60.0, 10.0);
+        for i in 0..200 {
+            let (dlat, dlng) = jitter_offset(0, &format!("frc{i}"), 20.0);
+            let moved = geo::distance_m(lat, lng, lat + dlat, lng + dlng);
+            assert!(moved <= 20.0 + 1e-6, "frc{i} moved {moved} m");
+        }
+    }
+
+    #[test]
+    fn ignored_objects_are_not_spread() {
+        let team = |key: &str, ignore: bool| {
+            let mut tba: TbaTeam = serde_json::from_value(serde_json::json!({"key": key})).unwrap();
+            (tba.lat, tba.lng) = (Some(37.0), Some(-122.0));
+            let mut team = TeamData::new(tba);
+            team.ignore = ignore.then_some(true);
+            (key.to_string(), team)
+        };
+        let mut teams =
+            HashMap::from([team("frc1", false), team("frc2", false), team("frc3", true)]);
+        apply(&mut teams, &LayoutSettings::default(), "Team");
+        assert_eq!(teams["frc1"].colocated_with, ["frc2"]);
+        assert_eq!(teams["frc2"].colocated_with, ["frc1"]);
+        assert!(teams["frc3"].colocated_with.is_empty());
+        assert!(teams["frc3"].display_offset.is_none());
+    }
+}
@@ -1,426 +1,591 @@
+pub mod archive;
+pub mod cache;
+pub mod coords;
+pub mod external;
+pub mod gazetteer;
+pub mod layout;
+pub mod normalize;
+pub mod outliers;
+pub mod override_formats;
+pub mod overrides;
+pub mod plus_code;
+pub mod quota;
+pub mod resolve;
+pub mod stability;
 pub mod types;
+pub mod validate;
 
 use std::{
-    collections::HashMap,
-    fs,
+    collections::{BTreeSet, HashMap},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
 };
 
-use anyhow::Result as AnyhowResult;
+use anyhow::{Context, Result as AnyhowResult};
+use chrono::{Duration, Utc};
 use log::{error, info, warn};
-use rand::Rng;
-use regex::Regex;
 use reqwest_middleware::ClientWithMiddleware;
-use serde_json::Value;
+use serde::de::DeserializeOwned;
 
 use crate::{
+    config::{LayoutSettings, LocalitySettings, ResolutionSettings, Settings, StabilitySettings},
     first_api::FirstApiClient,
-    geocoder::types::GeocodeLocation,
-    geocoder::types::{GeocodeResponse, LocationDict, LocationOverride},
-    map_types::{EventData, HasLocation, TeamData},
+    geocoder::archive::{Archive, ArchiveInvalidation, VenueRegistry},
+    geocoder::cache::{CachedGeocode, FailedGeocode, GeocodeCache},
+    geocoder::external::{ExternalConflict, ExternalSources},
+    geocoder::gazetteer::{Gazetteer, GazetteerMatch, Locality},
+    geocoder::normalize::AddressParts,
+    geocoder::quota::QuotaLedger,
+    geocoder::resolve::ResolveContext,
+    geocoder::types::{
+        GeocodeMatch, GeocodeResponse, GeocodeResult, LocationDict, LocationOverride,
+        PlaceDetailsResponse,
+    },
+    map_types::{EventData, FilledLocality, HasLocation, TeamData},
     tba::types::{TbaEvent, TbaTeam},
 };
 
 // ── Address builders ──────────────────────────────────────────
 
-fn make_team_address(team: &TbaTeam) -> Option<String> {
-    let parts = [
-        team.school_name.as_deref().unwrap_or(""),
-        team.city.as_deref().unwrap_or(""),
-        team.state_prov.as_deref().unwrap_or(""),
-        team.postal_code.as_deref().unwrap_or(""),
-        team.country.as_deref().unwrap_or(""),
-    ];
-    let addr: String = parts
-        .join(" ")
-        .split_whitespace()
-        .collect::<Vec<_>>()
-        .join(" ");
-    if addr.is_empty() { None } else { Some(addr) }
+pub(crate) fn make_team_address(team: &TbaTeam) -> Option<String> {
+    normalize::build_address(&AddressParts {
+        name: team.school_name.as_deref(),
+        street: None,
+        city: team.city.as_deref(),
+        state_prov: team.state_prov.as_deref(),
+        postal_code: team.postal_code.as_deref(),
+        country: team.country.as_deref(),
+    })
 }
 
-fn make_event_address(event: &TbaEvent) -> Option<String> {
-    let parts = [
-        event.venue.as_deref().unwrap_or(""),
-        event.address.as_deref().unwrap_or(""),
-        event.city.as_deref().unwrap_or(""),
-        event.state_prov.as_deref().unwrap_or(""),
-        event.postal_code.as_deref().unwrap_or(""),
-        event.country.as_deref().unwrap_or(""),
-    ];
-    let addr = parts.join(" ").trim().to_string();
-    if addr.is_empty() { None } else { Some(addr) }
+pub(crate) fn make_event_address(event: &TbaEvent) -> Option<String> {
+    normalize::build_address(&AddressParts {
+        name: event.venue.as_deref(),
+        street: event.address.as_deref(),
+        city: event.city.as_deref(),
+        state_prov: event.state_prov.as_deref(),
+        postal_code: event.postal_code.as_deref(),
+        country: event.country.as_deref(),
+    })
 }
 
 // ── Geocoder ───────────────────────────────────────────────────
 
+/// Provider name used for Google results in the geocode cache.
+const GOOGLE_PROVIDER: &str = "google";
+
 pub struct FrcGeocoder {
     client: Arc<ClientWithMiddleware>,
     gmaps_key: String,
     archive_path: PathBuf,
     team_overrides: LocationDict,
     event_overrides: LocationDict,
-    team_archive: HashMap<String, LocationOverride>,
-    event_archive: HashMap<String, LocationOverride>,
+    external: ExternalSources,
+    /// Distance beyond which differing override locations are a conflict.
+    conflict_radius_m: f64,
+    team_archive: Archive,
+    event_archive: Archive,
+    venues: VenueRegistry,
+    resolution: ResolutionSettings,
+    layout: LayoutSettings,
+    locality: LocalitySettings,
+    stability: StabilitySettings,
+    quota: Mutex<QuotaLedger>,
+    cache: Mutex<GeocodeCache>,
+    gazetteer: Gazetteer,
+    /// Keys that still need a geocode but were skipped because the budget ran out.
+    pending: Mutex<BTreeSet<String>>,
+    /// How long to wait before retrying a query the provider could not resolve.
+    retry_failed_after: Duration,
+    /// Archived locations dropped because the address changed.
+    invalidated: Mutex<Vec<ArchiveInvalidation>>,
+    /// Keys whose override and external source locations disagree.
+    conflicts: Mutex<Vec<ExternalConflict>>,
     pub first_api: FirstApiClient,
 }
 
 impl FrcGeocoder {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         client: Arc<ClientWithMiddleware>,
         gmaps_key: String,
         archive_path: PathBuf,
+        cache_path: &Path,
         team_overrides: LocationDict,
         event_overrides: LocationDict,
+        external: ExternalSources,
         first_api: FirstApiClient,
+        settings: &Settings,
     ) -> Self {
-        let team_archive = Self::read_team_archive(&archive_path);
-        let event_archive = Self::read_event_archive(&archive_path);
+        let team_archive = archive::read_team_archive(&archive_path);
+        let event_archive = archive::read_event_archive(&archive_path);
+        let venues = archive::read_venue_registry(&archive_path);
+        let quota = Mutex::new(QuotaLedger::load(&archive_path, &settings.geocoding));
+        let cache = Mutex::new(GeocodeCache::load(cache_path));
+        let gazetteer = Gazetteer::load(&settings.gazetteer.path);
         Self {
             client,
             gmaps_key,
             archive_path,
             team_overrides,
             event_overrides,
+            external,
+            conflict_radius_m: settings.external.conflict_radius_m,
             team_archive,
             event_archive,
+            venues,
+            resolution: settings.resolution.clone(),
+            layout: settings.layout.clone(),
+            locality: settings.locality.clone(),
+            stability: settings.stability.clone(),
+            quota,
+            cache,
+            gazetteer,
+            pending: Mutex::new(BTreeSet::new()),
+            retry_failed_after: Duration::days(settings.geocoding.retry_failed_after_days.into()),
+            invalidated: Mutex::new(Vec::new()),
+            conflicts: Mutex::new(Vec::new()),
             first_api,
         }
     }
 
-    // ── Archive I/O ────────────────────────────────────────────
+    /// Addresses the provider could not resolve, oldest first.
+    pub fn geocode_failures(&self) -> Vec<FailedGeocode> {
+        let mut failures: Vec<FailedGeocode> =
+            self.cache.lock().unwrap().failures().cloned().collect();
+        failures.sort_by_key(|f| f.failed_at);
+        failures
+    }
 
-    fn read_team_archive(archive_path: &Path) -> HashMap<String, LocationOverride> {
-        let re = Regex::new(r"^all_team_locations_(\d{4})\.json$").unwrap();
-        let mut years: HashMap<i64, PathBuf> = HashMap::new();
+    /// Keys skipped because the geocode budget was used up, sorted.
+    pub fn pending_geocodes(&self) -> Vec<String> {
+        self.pending.lock().unwrap().iter().cloned().collect()
+    }
 
-        if let Ok(entries) = std::fs::read_dir(archive_path) {
-            for entry in entries.flatten() {
-                let name = entry.file_name().to_string_lossy().to_string();
-                if let Some(caps) = re.captures(&name)
-                    && let Ok(y) = caps[1].parse::<i64>()
-                {
-                    years.insert(y, entry.path());
-                }
-            }
-        }
+    /// Archived locations re-resolved because their address changed, sorted by key.
+    pub fn archive_invalidations(&self) -> Vec<ArchiveInvalidation> {
+        let mut invalidated = self.invalidated.lock().unwrap().clone();
+        invalidated.sort_by(|a, b| a.key.cmp(&b.key));
+        invalidated
+    }
 
-        if let Some((max_year, path)) = years.iter().max_by_key(|(y, _)| *y) {
-            info!(
-                "Using team location archive: {} (year {})",
-                path.display(),
-                *max_year
-            );
-            match std::fs::read_to_string(path) {
-                Ok(content) => match serde_json::from_str(&content) {
-                    Ok(map) => return map,
-                    Err(e) => warn!("Failed to parse team archive: {}", e),
-                },
-                Err(e) => warn!("Failed to read team archive: {}", e),
-            }
-        } else {
+    /// Keys whose override and external source locations disagree, teams
+    /// first, each sorted by key.
+    pub fn external_conflicts(&self) -> Vec<ExternalConflict> {
+        self.conflicts.lock().unwrap().clone()
+    }
+
+    /// Record the conflicts between override sources for `keys`.
+    fn check_conflicts<'a>(
+        &self,
+        keys: impl IntoIterator<Item = &'a String>,
+        overrides: &LocationDict,
+        external: &[external::ExternalSource],
+        year: u32,
+    ) {
+        let found =
+            external::find_conflicts(keys, overrides, external, year, self.conflict_radius_m);
+        for conflict in &found {
+            let sources: Vec<&str> = conflict
+                .locations
+                .iter()
+                .map(|l| l.source.as_str())
+                .collect();
             warn!(
-                "Team archive not available, geocoding will take a while and incur several API requests."
+                "Locations for {} differ by {} m between {}; using {}",
+                conflict.key,
+                conflict.distance_m,
+                sources.join(", "),
+                sources[0]
             );
         }
-        HashMap::new()
+        self.conflicts.lock().unwrap().extend(found);
     }
 
-    fn read_event_archive(archive_path: &Path) -> HashMap<String, LocationOverride> {
-        let path = archive_path.join("all_event_locations.json");
-        if path.is_file() {
-            match std::fs::read_to_string(&path) {
-                Ok(content) => match serde_json::from_str(&content) {
-                    Ok(map) => return map,
-                    Err(e) => warn!("Failed to parse event archive: {}", e),
-                },
-                Err(e) => warn!("Failed to read event archive: {}", e),
-            }
-        } else {
+    // ── Google Maps Geocoding ──────────────────────────────────
+
+    /// Reserve a paid geocode call for `key`, recording it as pending if the
+    /// budget is used up.
+    fn reserve_geocode(&self, key: &str) -> bool {
+        if self.quota.lock().unwrap().try_acquire() {
+            return true;
+        }
+        // Several paid steps may be refused for the same key; report it once.
+        if self.pending.lock().unwrap().insert(key.to_string()) {
+            warn!("Geocode budget exhausted, leaving {} pending", key);
+        }
+        false
+    }
+
+    /// Persist the quota ledger and geocode cache, and report keys left pending.
+    fn finish_geocoding(&self, obj_type: &str) {
+        if let Err(e) = self.quota.lock().unwrap().save() {
+            error!("Failed to save geocode quota ledger: {}", e);
+        }
+        if let Err(e) = self.cache.lock().unwrap().save() {
+            error!("Failed to save geocode cache: {}", e);
+        }
+        let pending = self.pending.lock().unwrap();
+        if !pending.is_empty() {
             warn!(
-                "Event archive not available, geocoding will take a while and incur several API requests."
+                "{} geocode(s) pending after {} pass due to budget: {}",
+                pending.len(),
+                obj_type,
+                pending.iter().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+        let failures = self.cache.lock().unwrap().failures().count();
+        if failures > 0 {
+            warn!(
+                "{} address(es) have failed to geocode; list them with `geocode-cache failures`",
+                failures
             );
         }
-        HashMap::new()
     }
 
-    fn save_team_archive(&self, teams: &HashMap<String, TeamData>, year: u32) {
-        let data: HashMap<String, Value> = teams
-            .iter()
-            .filter(|(_, v)| v.has_location() && v.ignore != Some(true))
-            .map(|(k, v)| {
-                let entry = serde_json::json!({
-                    "lat": v.tba.lat,
-                    "lng": v.tba.lng,
-                });
-                (k.clone(), entry)
-            })
-            .collect();
-
-        let name = format!("all_team_locations_{}.json", year);
-        let path = self.archive_path.join(name);
-        std::fs::create_dir_all(&self.archive_path).ok();
-        if let Err(e) = std::fs::write(&path, serde_json::to_string(&data).unwrap_or_default()) {
-            error!("Failed to save team archive: {}", e);
-        }
+    /// Look up a postal-code or city centroid in the offline gazetteer.
+    /// Free, and works without network access.
+    fn gazetteer_lookup(&self, locality: &Locality) -> Option<GazetteerMatch> {
+        self.gazetteer.lookup(locality)
     }
 
-    fn save_event_archive(&self, events: &HashMap<String, EventData>) {
-        let data: HashMap<String, Value> = events
+    /// Fill `filled_locality` for located teams that TBA gives no city or
+    /// country, from the nearest gazetteer city. Only the fields TBA leaves
+    /// blank are filled.
+    fn fill_localities(&self, teams: &mut HashMap<String, TeamData>) {
+        let radius_m = self.locality.max_distance_km * 1000.0;
+        let blank = |field: Option<&str>| field.is_none_or(|v| v.trim().is_empty());
+        let mut keys: Vec<String> = teams
             .iter()
-            .filter(|(_, v)| v.has_location() && v.ignore != Some(true))
-            .map(|(k, v)| {
-                let entry = serde_json::json!({
-                    "lat": v.tba.lat,
-                    "lng": v.tba.lng,
-                });
-                (k.clone(), entry)
+            .filter(|(_, team)| {
+                let locality = team.locality();
+                team.has_location()
+                    && !team.is_ignored()
+                    && (blank(locality.city) || blank(locality.country))
             })
+            .map(|(key, _)| key.clone())
             .collect();
+        if keys.is_empty() {
+            return;
+        }
+        keys.sort();
 
-        let path = self.archive_path.join("all_event_locations.json");
-        std::fs::create_dir_all(&self.archive_path).ok();
-        if let Err(e) = std::fs::write(&path, serde_json::to_string(&data).unwrap_or_default()) {
-            error!("Failed to save event archive: {}", e);
+        let mut missed = Vec::new();
+        for key in &keys {
+            let team = teams.get_mut(key).unwrap();
+            let (lat, lng) = (team.lat().unwrap(), team.lng().unwrap());
+            let Some(place) = self
+                .gazetteer
+                .places_near(lat, lng, radius_m)
+                .into_iter()
+                .next()
+            else {
+                missed.push(key.as_str());
+                continue;
+            };
+            let locality = team.locality();
+            let filled = FilledLocality {
+                city: blank(locality.city).then_some(place.name),
+                state_prov: place.admin1.filter(|_| blank(locality.state_prov)),
+                country: blank(locality.country).then(|| {
+                    gazetteer::country_name(&place.country)
+                        .map(str::to_string)
+                        .unwrap_or(place.country)
+                }),
+                distance_km: (place.distance_m / 100.0).round() / 10.0,
+            };
+            let parts = [&filled.city, &filled.state_prov, &filled.country];
+            let described: Vec<&str> = parts.into_iter().flatten().map(String::as_str).collect();
+            info!("Filled locality for {}: {}", key, described.join(", "));
+            team.filled_locality = Some(filled);
+        }
+        if !missed.is_empty() {
+            warn!(
+                "No gazetteer city within {} km to fill the locality of {}",
+                self.locality.max_distance_km,
+                missed.join(", ")
+            );
         }
     }
 
-    // ── Google Maps Geocoding ──────────────────────────────────
-
-    async fn geocode_address(&self, address: &str) -> Option<GeocodeLocation> {
-        let url = format!(
-            "https://maps.googleapis.com/maps/api/geocode/json?address={}&key={}",
-            urlencoding::encode(address),
-            &self.gmaps_key,
-        );
-
-        match self.client.get(&url).send().await {
-            Ok(resp) => match resp.json::<GeocodeResponse>().await {
-                Ok(parsed) => {
-                    let Some(loc) = parsed
-                        .results
-                        .into_iter()
-                        .next()
-                        .and_then(|r| r.geometry)
-                        .and_then(|g| g.location)
-                    else {
-                        warn!("Geocode response for '{}' has no results!", address);
-                        return None;
-                    };
-
-                    Some(loc)
-                }
-                Err(e) => {
-                    error!("Failed to parse geocode response: {}", e);
-                    None
-                }
-            },
+    /// Send a Google Maps request and parse the JSON body. Releases the
+    /// caller's budget reservation if the response came from the HTTP cache.
+    async fn google_get<T: DeserializeOwned>(&self, url: &str) -> Option<T> {
+        let resp = match self.client.get(url).send().await {
+            Ok(resp) => resp,
             Err(e) => {
                 error!("Geocode HTTP request failed: {}", e);
+                return None;
+            }
+        };
+        if resp.headers().get("x-cache").is_some_and(|v| v == "HIT") {
+            self.quota.lock().unwrap().release();
+        }
+        match resp.json::<T>().await {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                error!("Failed to parse geocode response: {}", e);
                 None
             }
         }
     }
 
-    async fn geolocate_team(&self, team: &mut TeamData) {
-        let addr = make_team_address(&team.tba);
-        let key = team.tba.key.clone();
-        match addr {
-            None => {
-                error!("Team {} has no address.", key);
-                team.clear_location();
-            }
-            Some(addr) => {
-                info!("Address for {}: {}", key, addr);
-                match self.geocode_address(&addr).await {
-                    Some(loc) => {
-                        team.set_lat_lng(loc.lat, loc.lng);
-                        info!("Location: ({}, {})", loc.lat, loc.lng);
-                    }
-                    None => {
-                        error!("Could not geocode address for team {}", key);
-                        team.clear_location();
-                    }
-                }
-            }
+    /// Whether `query` failed recently enough that it shouldn't be retried yet.
+    fn recently_failed(&self, key: &str, source: GeocodeMatch, query: &str) -> bool {
+        let cache = self.cache.lock().unwrap();
+        let Some(failure) = cache.failure(GOOGLE_PROVIDER, source, query) else {
+            return false;
+        };
+        let retry_at = failure.failed_at + self.retry_failed_after;
+        if retry_at <= Utc::now() {
+            return false;
         }
+        info!(
+            "Skipping geocode for {}: '{}' failed on {} ({}); retrying after {}",
+            key,
+            query,
+            failure.failed_at.date_naive(),
+            failure.reason,
+            retry_at.date_naive()
+        );
+        true
     }
 
-    async fn geolocate_event(&self, event: &mut EventData) {
-        let addr = make_event_address(&event.tba);
-        let key = event.tba.key.clone();
-        match addr {
-            None => {
-                error!("Event {} has no address.", key);
-                event.clear_location();
-            }
-            Some(addr) => {
-                info!("Address for {}: {}", key, addr);
-                match self.geocode_address(&addr).await {
-                    Some(loc) => {
-                        event.set_lat_lng(loc.lat, loc.lng);
-                        info!("Location: ({}, {})", loc.lat, loc.lng);
-                    }
-                    None => {
-                        error!("Could not geocode address for event {}", key);
-                        event.clear_location();
-                    }
-                }
-            }
-        }
+    /// Remember a failed query so it isn't retried until the retry-after delay passes.
+    fn record_failure(&self, key: &str, source: GeocodeMatch, query: &str, reason: String) {
+        self.cache
+            .lock()
+            .unwrap()
+            .record_failure(GOOGLE_PROVIDER, source, query, key, reason);
     }
 
-    // ── Location deduplication ─────────────────────────────────
+    /// Pull the location out of a Google result and remember it in the geocode cache.
+    fn record_result(
+        &self,
+        key: &str,
+        source: GeocodeMatch,
+        query: &str,
+        result: GeocodeResult,
+    ) -> Option<CachedGeocode> {
+        let Some((loc, location_type)) = result
+            .geometry
+            .and_then(|g| Some((g.location?, g.location_type)))
+        else {
+            warn!("Geocode result for '{}' has no location!", query);
+            self.record_failure(key, source, query, "result has no location".to_string());
+            return None;
+        };
+
+        let entry = CachedGeocode {
+            query: query.to_string(),
+            match_source: source,
+            lat: loc.lat,
+            lng: loc.lng,
+            formatted_address: result.formatted_address,
+            place_id: result.place_id,
+            location_type,
+            partial_match: result.partial_match,
+            fetched_at: Utc::now(),
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(GOOGLE_PROVIDER, entry.clone());
+        Some(entry)
+    }
 
-    fn dedup_locations<T: HasLocation>(objects: &mut HashMap<String, T>, obj_type: &str) {
-        let mut seen: HashMap<(u64, u64), String> = HashMap::new();
-        let mut to_jitter: Vec<String> = Vec::new();
+    /// Geocode an address for `key`, consulting the geocode cache first. A
+    /// paid call is only reserved on a cache miss.
+    async fn geocode_address(&self, key: &str, address: &str) -> Option<CachedGeocode> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(GOOGLE_PROVIDER, GeocodeMatch::Address, address)
+            .cloned();
+        if cached.is_some() {
+            info!("Geocode cache hit for '{}'", address);
+            return cached;
+        }
+        if self.recently_failed(key, GeocodeMatch::Address, address) {
+            return None;
+        }
+        if !self.reserve_geocode(key) {
+            return None;
+        }
 
-        // First pass: find collisions.
-        for (key, obj) in objects.iter() {
-            if !obj.has_location() {
-                continue;
-            }
-            let lat = obj.lat().unwrap();
-            let lng = obj.lng().unwrap();
-            let loc_key = (lat.to_bits(), lng.to_bits());
-            if let Some(existing) = seen.get(&loc_key) {
-                warn!(
-                    "{} {} location overlaps with {}, randomizing a bit!",
-                    obj_type, key, existing,
-                );
-                to_jitter.push(key.clone());
-            } else {
-                seen.insert(loc_key, key.clone());
+        let url = format!(
+            "https://maps.googleapis.com/maps/api/geocode/json?address={}&key={}",
+            urlencoding::encode(address),
+            &self.gmaps_key,
+        );
+        let parsed: GeocodeResponse = self.google_get(&url).await?;
+        let Some(result) = parsed.results.into_iter().next() else {
+            warn!(
+                "Geocode response for '{}' has no results! (status {})",
+                address, parsed.status
+            );
+            if is_permanent_failure(&parsed.status) {
+                let reason = format!("no results (status {})", parsed.status);
+                self.record_failure(key, GeocodeMatch::Address, address, reason);
             }
+            return None;
+        };
+        self.record_result(key, GeocodeMatch::Address, address, result)
+    }
+
+    /// Resolve a Google place id through Place Details, consulting the
+    /// geocode cache first. Only the geometry fields are requested.
+    async fn place_details(&self, key: &str, place_id: &str) -> Option<CachedGeocode> {
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(GOOGLE_PROVIDER, GeocodeMatch::PlaceId, place_id)
+            .cloned();
+        if cached.is_some() {
+            info!("Geocode cache hit for place id {}", place_id);
+            return cached;
+        }
+        if self.recently_failed(key, GeocodeMatch::PlaceId, place_id) {
+            return None;
+        }
+        if !self.reserve_geocode(key) {
+            return None;
         }
 
-        // Second pass: apply jitter.
-        let mut rng = rand::rng();
-        for key in to_jitter {
-            if let Some(obj) = objects.get_mut(&key) {
-                jitter_location(obj, &mut rng);
+        let url = format!(
+            "https://maps.googleapis.com/maps/api/place/details/json?place_id={}&fields=geometry,formatted_address,place_id&key={}",
+            urlencoding::encode(place_id),
+            &self.gmaps_key,
+        );
+        let parsed: PlaceDetailsResponse = self.google_get(&url).await?;
+        let Some(result) = parsed.result else {
+            warn!(
+                "Place Details for {} returned no result! (status {})",
+                place_id, parsed.status
+            );
+            if is_permanent_failure(&parsed.status) {
+                let reason = format!("no result (status {})", parsed.status);
+                self.record_failure(key, GeocodeMatch::PlaceId, place_id, reason);
             }
-        }
+            return None;
+        };
+        self.record_result(key, GeocodeMatch::PlaceId, place_id, result)
     }
 
     // ── Public API ─────────────────────────────────────────────
 
-    pub async fn populate_team_locations(&self, teams: &mut HashMap<String, TeamData>, year: u32) {
+    pub async fn populate_team_locations(
+        &self,
+        teams: &mut HashMap<String, TeamData>,
+        year: u32,
+    ) -> AnyhowResult<()> {
         info!("Geolocating teams.");
 
-        let keys: Vec<String> = teams.keys().cloned().collect();
-        for key in &keys {
-            let team = teams.get_mut(key).unwrap();
-
-            // Priority 1: manual override
-            if let Some(ov) = self.team_overrides.get(key.as_str()) {
-                apply_override(team, ov);
-            }
-            // Priority 2: archive
-            else if let Some(ov) = self.team_archive.get(key.as_str()) {
-                apply_override(team, ov);
-            }
-            // Priority 3: geocode
-            else {
-                warn!("Geocoding team {}", key);
-                self.geolocate_team(team).await;
-            }
+        let ctx = ResolveContext {
+            year,
+            chain: &self.resolution.teams,
+            overrides: &self.team_overrides,
+            external: &self.external.teams,
+            archive: &self.team_archive,
+            venues: &VenueRegistry::new(),
+        };
+        self.check_conflicts(teams.keys(), ctx.overrides, ctx.external, year);
+        self.resolve_locations(teams, &ctx).await;
+        if self.locality.fill_missing {
+            self.fill_localities(teams);
         }
 
-        Self::dedup_locations(teams, "Team");
-        self.save_team_archive(teams, year);
+        self.finish_geocoding("team");
+        if self.stability.gate {
+            // Before anything of this run is archived or published.
+            let resolved = archive::build_archive(teams);
+            stability::check_gate(&self.archive_path, year, &resolved, &self.stability)?;
+        }
+        self.save_team_archive(teams, year)?;
+        layout::apply(teams, &self.layout, "Team");
         info!("Geolocating teams finished.");
+        Ok(())
     }
 
     pub async fn populate_event_locations(
         &self,
         events: &mut HashMap<String, EventData>,
         year: u32,
-    ) {
+    ) -> AnyhowResult<()> {
         info!("Geolocating events.");
 
-        let keys: Vec<String> = events.keys().cloned().collect();
-        for key in &keys {
-            let event = events.get_mut(key).unwrap();
-
-            // Priority 1: manual override
-            if let Some(ov) = self.event_overrides.get(key.as_str()) {
-                apply_override(event, ov);
-            }
-
+        let ctx = ResolveContext {
+            year,
+            chain: &self.resolution.events,
+            overrides: &self.event_overrides,
+            external: &self.external.events,
+            archive: &self.event_archive,
+            venues: &self.venues,
+        };
+        self.check_conflicts(events.keys(), ctx.overrides, ctx.external, year);
+        self.resolve_locations(events, &ctx).await;
+
+        // If an event still has no location, mark it as ignored
+        for (key, event) in events.iter_mut() {
             if !event.has_location() {
-                // Try archive
-                if let Some(ov) = self.event_archive.get(key.as_str()) {
-                    apply_override(event, ov);
-                }
-                // Otherwise, if official, enhance + geocode
-                else if event.is_official {
-                    warn!("Geocoding event {}", key);
-                    // Try to enhance with FIRST API data
-                    let first_event_code = event.tba.first_event_code.clone();
-                    if let Some(ref code) = first_event_code {
-                        let mut venue = event.tba.venue.clone();
-                        let mut address = event.tba.address.clone();
-                        if let Err(e) = self
-                            .first_api
-                            .enhance_event_data(year as i64, code, &mut venue, &mut address)
-                            .await
-                        {
-                            error!("Failed to fetch FIRST data for event {}: {}", key, e);
-                        }
-                        event.tba.venue = venue;
-                        event.tba.address = address;
-                    }
-                    self.geolocate_event(event).await;
+                event.ignore = Some(true);
+                if event.is_official {
+                    error!("Event {} has no location!", key);
                 } else {
                     error!("Event {} is not official and could not be geocoded!", key);
                 }
             }
-
-            // If event still has no location, mark it as ignored
-            if !event.has_location() {
-                event.ignore = Some(true);
-                error!("Event {} has no location!", key);
-            }
         }
 
-        self.save_event_archive(events);
-        Self::dedup_locations(events, "Event");
+        self.finish_geocoding("event");
+        self.save_event_archive(events, year)?;
+        layout::apply(events, &self.layout, "Event");
         info!("Geolocating events finished.");
+        Ok(())
     }
 }
 
 // ── Free helpers ───────────────────────────────────────────────
 
-fn apply_override<T: HasLocation>(obj: &mut T, ov: &LocationOverride) {
+/// Whether a Google status means the query itself can't be resolved, as
+/// opposed to a transient or account problem that deserves a retry.
+fn is_permanent_failure(status: &str) -> bool {
+    matches!(
+        status,
+        "OK" | "ZERO_RESULTS" | "NOT_FOUND" | "INVALID_REQUEST"
+    )
+}
+
+/// Apply an override if it is valid in season `year`. Returns whether it was.
+pub(crate) fn apply_override<T: HasLocation>(
+    obj: &mut T,
+    ov: &LocationOverride,
+    year: u32,
+) -> bool {
+    if !ov.applies_to(year) {
+        return false;
+    }
     if let (Some(lat), Some(lng)) = (ov.lat, ov.lng) {
         obj.set_lat_lng(lat, lng);
     }
     if let Some(ignore) = ov.ignore {
         obj.set_ignore(ignore);
     }
+    true
 }
 
-fn jitter_location<T: HasLocation>(obj: &mut T, rng: &mut impl Rng) {
-    use rand_distr::{Distribution, Normal};
-    let normal = Normal::new(0.0, 0.001).unwrap();
-    if let (Some(lat), Some(lng)) = (obj.lat(), obj.lng()) {
-        let dlat: f64 = normal.sample(rng);
-        let dlng: f64 = normal.sample(rng);
-        obj.set_lat_lng(lat + dlat, lng + dlng);
-    }
-}
-
-/// Load a location override file (JSON), stripping the `_comment` key.
+/// Load a location override file (JSON, TOML or YAML, by extension),
+/// stripping the `_comment` key.
 pub fn load_location_file(path: &Path) -> AnyhowResult<LocationDict> {
-    let content = fs::read_to_string(path)?;
-    let mut map: HashMap<String, Value> = serde_json::from_str(&content)?;
-    map.remove("_comment");
     let mut result = LocationDict::new();
-    for (k, v) in map {
-        let entry: LocationOverride = serde_json::from_value(v)?;
+    for (k, v) in override_formats::read_entries(path)? {
+        if k == override_formats::COMMENT_KEY {
+            continue;
+        }
+        let entry: LocationOverride = serde_json::from_value(v)
+            .with_context(|| format!("Invalid override for {} in {}", k, path.display()))?;
         result.insert(k, entry);
     }
     Ok(result)
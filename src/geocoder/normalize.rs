@@ -0,0 +1,286 @@
+//! Address normalization shared by geocode queries and cache keys.
+//!
+//! TBA address fields are free text: the same school shows up as "Foo HS" and
+//! "Foo High School", event addresses repeat the city/state that also appear
+//! in their own fields, and some contain line breaks or full-width characters.
+//! Everything here turns those into one consistent form.
+
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+
+/// Abbreviations expanded inside school and venue names. "St" only becomes
+/// "Saint" in front of another word, so "Penn St" is left alone.
+const NAME_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("hs", "High School"),
+    ("ms", "Middle School"),
+    ("jhs", "Junior High School"),
+    ("jr", "Junior"),
+    ("sr", "Senior"),
+    ("sch", "School"),
+    ("acad", "Academy"),
+    ("ctr", "Center"),
+    ("univ", "University"),
+    ("inst", "Institute"),
+    ("st", "Saint"),
+    ("mt", "Mount"),
+    ("ft", "Fort"),
+    ("&", "and"),
+];
+
+/// Street suffixes, only expanded as the last word of an address line.
+const STREET_SUFFIXES: &[(&str, &str)] = &[
+    ("st", "Street"),
+    ("ave", "Avenue"),
+    ("av", "Avenue"),
+    ("rd", "Road"),
+    ("blvd", "Boulevard"),
+    ("dr", "Drive"),
+    ("hwy", "Highway"),
+    ("ln", "Lane"),
+    ("pkwy", "Parkway"),
+    ("ct", "Court"),
+    ("pl", "Place"),
+    ("cir", "Circle"),
+    ("ter", "Terrace"),
+];
+
+/// Country spellings folded into the form TBA uses most.
+const COUNTRY_ALIASES: &[(&str, &str)] = &[
+    ("us", "USA"),
+    ("usa", "USA"),
+    ("united states", "USA"),
+    ("united states of america", "USA"),
+    ("turkey", "Türkiye"),
+    ("turkiye", "Türkiye"),
+    ("uk", "United Kingdom"),
+    ("great britain", "United Kingdom"),
+    ("taiwan", "Chinese Taipei"),
+    ("republic of korea", "South Korea"),
+    ("korea", "South Korea"),
+];
+
+/// The pieces of an address, in the order they are joined.
+#[derive(Debug, Default)]
+pub struct AddressParts<'a> {
+    /// School or venue name.
+    pub name: Option<&'a str>,
+    /// Street address; may itself hold a full multi-line address.
+    pub street: Option<&'a str>,
+    pub city: Option<&'a str>,
+    pub state_prov: Option<&'a str>,
+    pub postal_code: Option<&'a str>,
+    pub country: Option<&'a str>,
+}
+
+/// Unicode-normalize (NFKC), turn line breaks into commas and collapse
+/// whitespace and empty comma-separated segments.
+pub fn clean_text(text: &str) -> String {
+    let text: String = text
+        .nfkc()
+        .map(|c| if c == '\r' || c == '\n' { ',' } else { c })
+        .collect();
+    text.split(',')
+        .map(|seg| seg.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|seg| !seg.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Comparison form of a string: lowercase, diacritics stripped, punctuation
+/// dropped and whitespace collapsed. Used for cache keys and de-duplication.
+pub fn fold(text: &str) -> String {
+    let stripped: String = text
+        .nfkd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect();
+    stripped.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn lookup(table: &[(&str, &'static str)], word: &str) -> Option<&'static str> {
+    let folded = fold(word);
+    // Symbols such as "&" fold to nothing, so match those verbatim.
+    let key = if folded.is_empty() { word } else { &folded };
+    table
+        .iter()
+        .find(|(abbr, _)| *abbr == key)
+        .map(|(_, full)| *full)
+}
+
+/// Expand abbreviations in a school or venue name and drop parenthesised
+/// asides such as "(Robotics Club)".
+pub fn canonicalize_name(name: &str) -> String {
+    let mut depth = 0usize;
+    let without_parens: String = name
+        .chars()
+        .filter(|c| match c {
+            '(' => {
+                depth += 1;
+                false
+            }
+            ')' => {
+                depth = depth.saturating_sub(1);
+                false
+            }
+            _ => depth == 0,
+        })
+        .collect();
+
+    let cleaned = clean_text(&without_parens);
+    let words: Vec<&str> = cleaned.split_whitespace().collect();
+    words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let bare = word.trim_end_matches(',');
+            let suffix = &word[bare.len()..];
+            let ends_phrase = !suffix.is_empty() || i + 1 == words.len();
+            if fold(bare) == "st" && ends_phrase {
+                return word.to_string();
+            }
+            match lookup(NAME_ABBREVIATIONS, bare) {
+                Some(full) => format!("{}{}", full, suffix),
+                None => word.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Expand a trailing street suffix on each line of a street address
+/// ("30 Herbert St" -> "30 Herbert Street").
+pub fn canonicalize_street(street: &str) -> String {
+    clean_text(street)
+        .split(", ")
+        .map(|line| {
+            let mut words: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+            if words.len() > 1
+                && let Some(last) = words.last_mut()
+                && let Some(full) = lookup(STREET_SUFFIXES, last)
+            {
+                *last = full.to_string();
+            }
+            words.join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn canonicalize_country(country: &str) -> String {
+    let cleaned = clean_text(country);
+    match lookup(COUNTRY_ALIASES, &cleaned) {
+        Some(canonical) => canonical.to_string(),
+        None => cleaned,
+    }
+}
+
+/// Join address parts into a single geocode query, skipping empty parts and
+/// parts that repeat an earlier component exactly (e.g. an event address
+/// whose lines already include its city and country). A multi-line street
+/// counts each line as a component. The name is only dropped if the rest of
+/// the address repeats it, never the other way round.
+pub fn build_address(parts: &AddressParts) -> Option<String> {
+    let components = [
+        parts.street.map(canonicalize_street),
+        parts.city.map(clean_text),
+        parts.state_prov.map(clean_text),
+        parts.postal_code.map(clean_text),
+        parts.country.map(canonicalize_country),
+    ];
+
+    let mut out: Vec<String> = Vec::new();
+    let mut seen: Vec<String> = Vec::new();
+    for part in components.into_iter().flatten() {
+        let folded = fold(&part);
+        if folded.is_empty() || seen.contains(&folded) {
+            continue;
+        }
+        seen.extend(part.split(", ").map(fold));
+        seen.push(folded);
+        out.push(part);
+    }
+
+    if let Some(name) = parts.name.map(canonicalize_name) {
+        let folded = fold(&name);
+        if !folded.is_empty() && !seen.contains(&folded) {
+            out.insert(0, name);
+        }
+    }
+
+    if out.is_empty() {
+        None
+    } else {
+        Some(out.join(", "))
+    }
+}
+
+/// Normalized key for an address, stable across formatting differences.
+pub fn address_key(address: &str) -> String {
+    fold(address)
+}
+
+/// Stable hash of an address's normalized key, as 16 hex digits (FNV-1a).
+/// Used to notice when an archived location's address has changed.
+pub fn address_hash(address: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in address_key(address).bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(name: Option<&str>, street: Option<&str>, city: &str, state: &str) -> String {
+        build_address(&AddressParts {
+            name,
+            street,
+            city: Some(city),
+            state_prov: Some(state),
+            postal_code: Some("77001"),
+            country: Some("USA"),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn keeps_city_named_in_street() {
+        assert_eq!(
+            address(Some("Houston HS"), Some("100 Houston St"), "Houston", "TX"),
+            "Houston High School, 100 Houston Street, Houston, TX, 77001, USA"
+        );
+        assert_eq!(
+            address(None, Some("1 Washington Ave"), "Washington", "DC"),
+            "1 Washington Avenue, Washington, DC, 77001, USA"
+        );
+    }
+
+    #[test]
+    fn drops_repeated_components() {
+        assert_eq!(
+            address(None, Some("1 Main St\nHouston\nUSA"), "Houston", "TX"),
+            "1 Main Street, Houston, USA, TX, 77001"
+        );
+        assert_eq!(
+            address(Some("Houston"), None, "Houston", "TX"),
+            "Houston, TX, 77001, USA"
+        );
+    }
+
+    #[test]
+    fn expands_saint_only_before_a_word() {
+        assert_eq!(canonicalize_name("Penn St"), "Penn St");
+        assert_eq!(
+            canonicalize_name("St. Louis Univ"),
+            "Saint Louis University"
+        );
+        assert_eq!(canonicalize_name("St Mary HS"), "Saint Mary High School");
+        assert_eq!(
+            address(Some("Penn St"), None, "State College", "PA"),
+            "Penn St, State College, PA, 77001, USA"
+        );
+    }
+}
@@ -0,0 +1,382 @@
+//! Review report of teams whose published location looks wrong for their
+//! stated city, state and country.
+//!
+//! Each team in a season output file is checked against the offline
+//! gazetteer: a location at (0, 0), one too far from the postal-code or city
+//! centroid of its locality, and one outside its stated country are flagged.
+//! Countries are approximated by the bounding boxes in [`COUNTRY_BOUNDS`],
+//! which cover a little more than the country, so a border town is never
+//! flagged but a point well inside a neighbour can slip through.
+
+use std::{collections::HashMap, fmt, path::Path};
+
+use anyhow::{Context, Result as AnyhowResult};
+use log::warn;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+    fsio, geo,
+    geocoder::{
+        gazetteer::{self, Gazetteer, GazetteerPrecision},
+        layout::natural_cmp,
+        resolve::LocationSource,
+    },
+    map_types::{HasLocation, TeamData},
+};
+
+/// How far around a flagged location to look for a city to name in the report.
+const NEAREST_PLACE_RADIUS_M: f64 = 50_000.0;
+
+/// Slack added to every side of a country's bounding boxes, in degrees.
+const BOUNDS_MARGIN_DEGREES: f64 = 0.25;
+
+/// South, west, north and east edges of a box, in degrees.
+type Bounds = (f64, f64, f64, f64);
+
+/// Bounding boxes of the countries in the
+/// gazetteer's country table, by ISO code. Countries with overseas parts
+/// have a box for each part teams are found in. A country missing here is
+/// not checked.
+const COUNTRY_BOUNDS: &[(&str, &[Bounds])] = &[
+    (
+        "US",
+        &[
+            (24.4, -125.0, 49.4, -66.9),
+            (51.2, -179.9, 71.5, -129.9),
+            (51.2, 172.4, 53.1, 180.0),
+            (18.9, -160.3, 22.3, -154.8),
+            (17.9, -67.3, 18.6, -65.2),
+            (17.6, -65.1, 18.5, -64.5),
+            (13.2, 144.6, 13.7, 145.0),
+        ],
+    ),
+    ("CA", &[(41.6, -141.1, 83.2, -52.6)]),
+    ("MX", &[(14.5, -118.4, 32.8, -86.7)]),
+    ("TR", &[(35.8, 25.6, 42.2, 44.9)]),
+    ("BR", &[(-33.8, -74.0, 5.3, -28.8)]),
+    ("CN", &[(18.1, 73.5, 53.6, 134.8)]),
+    ("AU", &[(-43.7, 112.9, -10.0, 153.7)]),
+    ("TW", &[(21.8, 118.1, 26.4, 122.1)]),
+    ("IN", &[(6.7, 68.1, 35.7, 97.4)]),
+    ("DO", &[(17.5, -72.0, 19.95, -68.3)]),
+    ("PL", &[(49.0, 14.1, 54.9, 24.2)]),
+    ("PA", &[(7.2, -83.1, 9.7, -77.1)]),
+    ("JP", &[(24.0, 122.9, 45.6, 146.0)]),
+    ("CO", &[(-4.3, -81.8, 13.4, -66.8)]),
+    ("KR", &[(33.1, 124.6, 38.7, 131.9)]),
+    ("NL", &[(50.7, 3.3, 53.6, 7.3)]),
+    ("IL", &[(29.4, 34.2, 33.4, 35.9)]),
+    ("CZ", &[(48.5, 12.0, 51.1, 18.9)]),
+    ("VN", &[(8.4, 102.1, 23.4, 109.5)]),
+    ("GB", &[(49.8, -8.7, 60.9, 1.8)]),
+    ("SE", &[(55.3, 11.0, 69.1, 24.2)]),
+    ("ZA", &[(-34.9, 16.4, -22.1, 32.9)]),
+    ("RO", &[(43.6, 20.2, 48.3, 29.7)]),
+    ("PH", &[(4.6, 116.9, 21.1, 126.6)]),
+    ("MY", &[(0.8, 99.6, 7.4, 119.3)]),
+    (
+        "FR",
+        &[
+            (41.3, -5.2, 51.1, 9.6),
+            (-21.4, 55.2, -20.8, 55.9),
+            (14.3, -61.9, 16.6, -60.8),
+            (2.1, -54.6, 5.8, -51.6),
+        ],
+    ),
+    ("HR", &[(42.4, 13.4, 46.6, 19.5)]),
+    ("BG", &[(41.2, 22.3, 44.2, 28.6)]),
+    ("BZ", &[(15.8, -89.3, 18.5, -87.4)]),
+    ("DE", &[(47.2, 5.8, 55.1, 15.1)]),
+    ("CH", &[(45.8, 5.9, 47.9, 10.5)]),
+    ("CL", &[(-56.0, -75.8, -17.4, -66.4)]),
+    (
+        "EC",
+        &[(-5.1, -81.1, 1.5, -75.1), (-1.5, -92.1, 1.7, -89.2)],
+    ),
+    ("PY", &[(-27.7, -62.7, -19.2, -54.2)]),
+    ("KZ", &[(40.5, 46.4, 55.5, 87.4)]),
+    ("AZ", &[(38.3, 44.7, 41.95, 50.9)]),
+    ("LY", &[(19.5, 9.3, 33.2, 25.2)]),
+    ("ET", &[(3.3, 32.9, 15.0, 48.0)]),
+];
+
+/// Whether a point is inside one of a country's bounding boxes, or `None`
+/// when the country has none.
+fn in_country(code: &str, lat: f64, lng: f64) -> Option<bool> {
+    let (_, boxes) = COUNTRY_BOUNDS.iter().find(|(c, _)| *c == code)?;
+    let m = BOUNDS_MARGIN_DEGREES;
+    Some(boxes.iter().any(|&(south, west, north, east)| {
+        (south - m..=north + m).contains(&lat) && (west - m..=east + m).contains(&lng)
+    }))
+}
+
+/// Anything this close to (0, 0) is treated as a missing coordinate.
+const NULL_ISLAND_DEGREES: f64 = 1e-4;
+
+/// Why a location was flagged.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Problem {
+    /// The coordinate is (0, 0), almost always a missing value.
+    NullIsland,
+    /// The location is far from the centroid of the stated locality.
+    FarFromLocality {
+        distance_km: f64,
+        centroid: GazetteerPrecision,
+    },
+    /// The location is outside the stated country.
+    CountryMismatch {
+        stated: String,
+        /// Nearest gazetteer city, to say where the location is instead.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nearest_place: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nearest_country: Option<String>,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::NullIsland => write!(f, "placed at (0, 0)"),
+            Problem::FarFromLocality {
+                distance_km,
+                centroid,
+            } => {
+                let what = match centroid {
+                    GazetteerPrecision::PostalCode => "postal code",
+                    GazetteerPrecision::City => "city",
+                };
+                write!(f, "{} km from its {}", distance_km, what)
+            }
+            Problem::CountryMismatch {
+                stated,
+                nearest_place,
+                nearest_country,
+            } => {
+                write!(f, "outside its stated country {}", stated)?;
+                if let (Some(place), Some(country)) = (nearest_place, nearest_country) {
+                    write!(f, " (nearest city: {}, {})", place, country)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A team whose location needs a look.
+#[derive(Debug, Clone, Serialize)]
+pub struct Outlier {
+    pub key: String,
+    pub locality: String,
+    pub lat: f64,
+    pub lng: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_source: Option<LocationSource>,
+    pub problems: Vec<Problem>,
+}
+
+/// City, state and country as one string, for the report.
+fn describe_locality(team: &TeamData) -> String {
+    let locality = team.locality();
+    [locality.city, locality.state_prov, locality.country]
+        .into_iter()
+        .flatten()
+        .filter(|part| !part.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Problems with one team's location, if any.
+fn check_team(gazetteer: &Gazetteer, team: &TeamData, max_distance_m: f64) -> Vec<Problem> {
+    let (Some(lat), Some(lng)) = (team.lat(), team.lng()) else {
+        return Vec::new();
+    };
+    if lat.abs() < NULL_ISLAND_DEGREES && lng.abs() < NULL_ISLAND_DEGREES {
+        return vec![Problem::NullIsland];
+    }
+
+    let mut problems = Vec::new();
+    let locality = team.locality();
+    if let Some(hit) = gazetteer.lookup(&locality) {
+        let distance_m = geo::distance_m(lat, lng, hit.lat, hit.lng);
+        if distance_m > max_distance_m {
+            problems.push(Problem::FarFromLocality {
+                distance_km: (distance_m / 1000.0).round(),
+                centroid: hit.precision,
+            });
+        }
+    }
+    if let Some(stated) = locality.country.and_then(gazetteer::country_code)
+        && in_country(stated, lat, lng) == Some(false)
+    {
+        let nearest = gazetteer
+            .places_near(lat, lng, NEAREST_PLACE_RADIUS_M)
+            .into_iter()
+            .next();
+        problems.push(Problem::CountryMismatch {
+            stated: stated.to_string(),
+            nearest_place: nearest.as_ref().map(|p| p.name.clone()),
+            nearest_country: nearest.map(|p| p.country),
+        });
+    }
+    problems
+}
+
+/// Read the teams from a season output file.
+fn read_season_teams(path: &Path) -> AnyhowResult<HashMap<String, TeamData>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut data: Value = serde_json::from_str(&content)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    let teams = data
+        .get_mut("teams")
+        .map(Value::take)
+        .with_context(|| format!("No teams in {}", path.display()))?;
+    Ok(serde_json::from_value(teams)?)
+}
+
+/// Handle the `outliers` subcommand: print the flagged teams of season `year`
+/// and optionally write them to `report` as JSON.
+pub fn run_command(
+    output_path: &Path,
+    gazetteer_path: &Path,
+    year: u32,
+    max_distance_km: f64,
+    report: Option<&Path>,
+) -> AnyhowResult<()> {
+    let season_path = output_path.join(format!("season_{}.json", year));
+    let teams = read_season_teams(&season_path)?;
+    let gazetteer = Gazetteer::load(gazetteer_path);
+    if gazetteer.is_empty() {
+        warn!(
+            "No gazetteer data in {}; only (0, 0) locations can be found",
+            gazetteer_path.display()
+        );
+    }
+
+    let mut keys: Vec<&String> = teams.keys().collect();
+    keys.sort_by(|a, b| natural_cmp(a, b));
+    let mut outliers = Vec::new();
+    for key in keys {
+        let team = &teams[key];
+        if team.is_ignored() {
+            continue;
+        }
+        let problems = check_team(&gazetteer, team, max_distance_km * 1000.0);
+        if problems.is_empty() {
+            continue;
+        }
+        let outlier = Outlier {
+            key: key.clone(),
+            locality: describe_locality(team),
+            lat: team.lat().unwrap_or_default(),
+            lng: team.lng().unwrap_or_default(),
+            location_source: team.location_source,
+            problems,
+        };
+        let problems: Vec<String> = outlier.problems.iter().map(|p| p.to_string()).collect();
+        let source = outlier
+            .location_source
+            .map(|s| format!(" via {}", s.name()))
+            .unwrap_or_default();
+        println!(
+            "{} ({}) at ({}, {}){}: {}",
+            outlier.key,
+            outlier.locality,
+            outlier.lat,
+            outlier.lng,
+            source,
+            problems.join("; ")
+        );
+        outliers.push(outlier);
+    }
+    println!("{} of {} teams flagged", outliers.len(), teams.len());
+
+    if let Some(path) = report {
+        fsio::write_atomic(path, serde_json::to_string_pretty(&outliers)? + "\n")?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tba::types::TbaTeam;
+
+    const POSTAL_CODES: &str =
+        "US\t95014\tCupertino\tCalifornia\tCA\t\t\t\t\t37.318\t-122.045\t4\n";
+    const CITIES: &str = "\
+1\tCupertino\tCupertino\t\t37.323\t-122.032\tP\tPPL\tUS\t\tCA\t\t\t\t60000
+2\tTijuana\tTijuana\t\t32.533\t-117.017\tP\tPPL\tMX\t\tBCN\t\t\t\t1900000
+3\tSan Diego\tSan Diego\t\t32.716\t-117.165\tP\tPPL\tUS\t\tCA\t\t\t\t1400000
+";
+
+    fn team(postal_code: Option<&str>, city: &str, country: &str, lat: f64, lng: f64) -> TeamData {
+        let mut tba: TbaTeam = serde_json::from_value(serde_json::json!({"key": "frc1"})).unwrap();
+        tba.postal_code = postal_code.map(str::to_string);
+        tba.city = Some(city.to_string());
+        tba.state_prov = Some("CA".to_string());
+        tba.country = Some(country.to_string());
+        tba.lat = Some(lat);
+        tba.lng = Some(lng);
+        TeamData::new(tba)
+    }
+
+    fn check(team: &TeamData) -> Vec<Problem> {
+        let gazetteer = Gazetteer::from_text(POSTAL_CODES, CITIES);
+        check_team(&gazetteer, team, 50_000.0)
+    }
+
+    #[test]
+    fn flags_null_island() {
+        let problems = check(&team(None, "Cupertino", "USA", 0.0, 0.00001));
+        assert!(matches!(problems[..], [Problem::NullIsland]));
+    }
+
+    #[test]
+    fn flags_far_from_locality() {
+        // At the postal code, then in Sacramento, ~190 km away.
+        assert!(check(&team(Some("95014"), "Cupertino", "USA", 37.32, -122.04)).is_empty());
+        let problems = check(&team(Some("95014"), "Cupertino", "USA", 38.58, -121.49));
+        assert!(matches!(
+            problems[..],
+            [Problem::FarFromLocality {
+                centroid: GazetteerPrecision::PostalCode,
+                ..
+            }]
+        ));
+        // Without a postal code the city centroid is used.
+        let problems = check(&team(None, "Cupertino", "USA", 38.58, -121.49));
+        assert!(matches!(
+            problems[..],
+            [Problem::FarFromLocality {
+                centroid: GazetteerPrecision::City,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn flags_country_mismatch() {
+        // A San Diego border point is inside the USA box, and Tijuana inside Mexico's.
+        assert!(check(&team(None, "San Diego", "USA", 32.55, -117.05)).is_empty());
+        assert!(check(&team(None, "Tijuana", "Mexico", 32.53, -117.02)).is_empty());
+        // Rural Quebec, far from any gazetteer city, claimed to be in Mexico.
+        let problems = check(&team(None, "Nowhere", "Mexico", 50.0, -70.0));
+        assert!(matches!(
+            &problems[..],
+            [Problem::CountryMismatch { stated, nearest_place: None, .. }] if stated == "MX"
+        ));
+        // Tijuana claimed to be in Canada names the city it's next to.
+        let problems = check(&team(None, "Tijuana", "Canada", 32.53, -117.02));
+        assert!(matches!(
+            &problems[..],
+            [Problem::CountryMismatch { nearest_place: Some(place), .. }] if place == "Tijuana"
+        ));
+        // Countries without bounds aren't checked.
+        assert!(check(&team(None, "Nowhere", "Atlantis", 50.0, -70.0)).is_empty());
+    }
+}
@@ -0,0 +1,872 @@
+//! Override files in JSON, TOML or YAML, picked by file extension.
+//!
+//! Every format reads into the same entries, so `load_location_file` gives
+//! the same `LocationDict` whichever one a file is written in. Editing keeps
+//! the rest of the file as it was: TOML through `toml_edit`, and YAML by
+//! rewriting only the lines of the entry that changed. Comments above an
+//! entry go with it, and comments inside a rewritten YAML entry stay with the
+//! field they were next to.
+
+use std::{cmp::Ordering, fmt, path::Path};
+
+use anyhow::{Context, Result as AnyhowResult};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, de, ser::SerializeMap};
+use serde_json::{Map, Value};
+use toml_edit::{DocumentMut, Item};
+
+use crate::geocoder::{layout::natural_cmp, overrides::OVERRIDE_FIELDS};
+
+pub(crate) const COMMENT_KEY: &str = "_comment";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// The format of a file from its extension; anything unknown is JSON.
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Format::Toml,
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Format::Json => "JSON",
+            Format::Toml => "TOML",
+            Format::Yaml => "YAML",
+        })
+    }
+}
+
+/// Top-level entries of an override file in file order, `_comment` included.
+pub fn read_entries(path: &Path) -> AnyhowResult<Vec<(String, Value)>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let format = Format::of(path);
+    parse_entries(format, &content)
+        .with_context(|| format!("Failed to parse {} as {}", path.display(), format))
+}
+
+pub(crate) fn parse_entries(format: Format, content: &str) -> AnyhowResult<Vec<(String, Value)>> {
+    Ok(match format {
+        Format::Json => serde_json::from_str::<OrderedMap>(content)?.0,
+        Format::Toml => content
+            .parse::<DocumentMut>()?
+            .iter()
+            .filter_map(|(key, item)| Some((key.to_string(), toml_to_json(item)?)))
+            .collect(),
+        Format::Yaml => {
+            // A file with only comments is an empty mapping.
+            let mapping: Option<serde_yaml_ng::Mapping> = serde_yaml_ng::from_str(content)?;
+            let mut entries = Vec::new();
+            for (key, value) in mapping.unwrap_or_default() {
+                let key = match key {
+                    serde_yaml_ng::Value::String(s) => s,
+                    serde_yaml_ng::Value::Number(n) => n.to_string(),
+                    other => anyhow::bail!("Keys must be strings, not {:?}", other),
+                };
+                entries.push((key, serde_json::to_value(value)?));
+            }
+            entries
+        }
+    })
+}
+
+/// JSON object entries in file order.
+struct OrderedMap(Vec<(String, Value)>);
+
+impl<'de> Deserialize<'de> for OrderedMap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct OrderedVisitor;
+        impl<'de> de::Visitor<'de> for OrderedVisitor {
+            type Value = OrderedMap;
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a JSON object")
+            }
+            fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<OrderedMap, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(OrderedMap(entries))
+            }
+        }
+        deserializer.deserialize_map(OrderedVisitor)
+    }
+}
+
+/// Fields of an entry in `OVERRIDE_FIELDS` order, then any others.
+fn ordered_fields(fields: &Map<String, Value>) -> Vec<(&str, &Value)> {
+    let known = OVERRIDE_FIELDS
+        .iter()
+        .filter_map(|name| fields.get(*name).map(|value| (*name, value)));
+    let others = fields
+        .iter()
+        .filter(|(name, _)| !OVERRIDE_FIELDS.contains(&name.as_str()))
+        .map(|(name, value)| (name.as_str(), value));
+    known.chain(others).collect()
+}
+
+/// Index of the first key that sorts after `key`, or `None` if it sorts last.
+fn insert_position<'a>(
+    keys: impl DoubleEndedIterator<Item = &'a str> + Clone,
+    key: &str,
+) -> Option<usize> {
+    let greater = |k: &str| natural_cmp(k, key) == Ordering::Greater;
+    // Most new keys belong at the end.
+    if keys.clone().next_back().is_none_or(|last| !greater(last)) {
+        return None;
+    }
+    keys.into_iter().position(greater)
+}
+
+/// An override file held for editing in its own format.
+pub enum Document {
+    Json(JsonDocument),
+    Toml(TomlDocument),
+    Yaml(YamlDocument),
+}
+
+impl Document {
+    /// An empty document. The comment becomes `_comment` in JSON and a header
+    /// comment otherwise.
+    pub fn new(format: Format, comment: Option<Value>) -> Self {
+        let header = comment.as_ref().map(|c| match c {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        });
+        match format {
+            Format::Json => Document::Json(JsonDocument {
+                comment,
+                entries: Vec::new(),
+            }),
+            Format::Toml => Document::Toml(TomlDocument {
+                doc: DocumentMut::new(),
+                header,
+            }),
+            Format::Yaml => Document::Yaml(YamlDocument::new(header)),
+        }
+    }
+
+    pub fn parse(format: Format, content: &str) -> AnyhowResult<Self> {
+        Ok(match format {
+            Format::Json => {
+                let mut entries = parse_entries(format, content)?;
+                let comment = entries
+                    .iter()
+                    .position(|(k, _)| k == COMMENT_KEY)
+                    .map(|i| entries.remove(i).1);
+                Document::Json(JsonDocument { comment, entries })
+            }
+            Format::Toml => Document::Toml(TomlDocument {
+                doc: content.parse()?,
+                header: None,
+            }),
+            Format::Yaml => Document::Yaml(YamlDocument::parse(content)?),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        match self {
+            Document::Json(doc) => doc.get(key),
+            Document::Toml(doc) => doc.get(key),
+            Document::Yaml(doc) => doc.get(key),
+        }
+    }
+
+    /// Replace the entry for `key`, or insert it in natural key order.
+    pub fn put(&mut self, key: &str, fields: Map<String, Value>) -> AnyhowResult<()> {
+        match self {
+            Document::Json(doc) => {
+                doc.put(key, fields);
+                Ok(())
+            }
+            Document::Toml(doc) => {
+                doc.put(key, &fields);
+                Ok(())
+            }
+            Document::Yaml(doc) => doc.put(key, &fields),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        match self {
+            Document::Json(doc) => doc.remove(key),
+            Document::Toml(doc) => doc.remove(key),
+            Document::Yaml(doc) => doc.remove(key),
+        }
+    }
+
+    pub fn render(&self) -> AnyhowResult<String> {
+        match self {
+            Document::Json(doc) => doc.render(),
+            Document::Toml(doc) => Ok(doc.render()),
+            Document::Yaml(doc) => Ok(doc.render()),
+        }
+    }
+}
+
+// ── JSON ───────────────────────────────────────────────────────
+
+/// Entries in file order, with `_comment` kept aside so it's written first.
+pub struct JsonDocument {
+    comment: Option<Value>,
+    entries: Vec<(String, Value)>,
+}
+
+impl JsonDocument {
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|(k, _)| k == key)
+    }
+
+    fn get(&self, key: &str) -> Option<Value> {
+        self.position(key).map(|i| self.entries[i].1.clone())
+    }
+
+    fn put(&mut self, key: &str, fields: Map<String, Value>) {
+        match self.position(key) {
+            Some(i) => self.entries[i].1 = Value::Object(fields),
+            None => {
+                let at = insert_position(self.entries.iter().map(|(k, _)| k.as_str()), key)
+                    .unwrap_or(self.entries.len());
+                self.entries
+                    .insert(at, (key.to_string(), Value::Object(fields)));
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        self.position(key).map(|i| self.entries.remove(i).1)
+    }
+
+    fn render(&self) -> AnyhowResult<String> {
+        let mut json = Vec::new();
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+        let mut ser = serde_json::Serializer::with_formatter(&mut json, formatter);
+        OrderedEntries {
+            comment: self.comment.as_ref(),
+            entries: &self.entries,
+        }
+        .serialize(&mut ser)
+        .context("Failed to serialize override file")?;
+        json.push(b'\n');
+        Ok(String::from_utf8(json)?)
+    }
+}
+
+/// Override entries in file order, with `_comment` first.
+struct OrderedEntries<'a> {
+    comment: Option<&'a Value>,
+    entries: &'a [(String, Value)],
+}
+
+impl Serialize for OrderedEntries<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let len = self.entries.len() + usize::from(self.comment.is_some());
+        let mut map = serializer.serialize_map(Some(len))?;
+        if let Some(comment) = self.comment {
+            map.serialize_entry(COMMENT_KEY, comment)?;
+        }
+        for (key, value) in self.entries {
+            map.serialize_entry(key, &OrderedFields(value))?;
+        }
+        map.end()
+    }
+}
+
+/// An override entry with its fields in `OVERRIDE_FIELDS` order.
+struct OrderedFields<'a>(&'a Value);
+
+impl Serialize for OrderedFields<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Value::Object(fields) = self.0 else {
+            return self.0.serialize(serializer);
+        };
+        let mut map = serializer.serialize_map(Some(fields.len()))?;
+        for (name, value) in ordered_fields(fields) {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+// ── TOML ───────────────────────────────────────────────────────
+
+/// A TOML document, one `[key]` table per entry.
+pub struct TomlDocument {
+    doc: DocumentMut,
+    /// Header comment for a new file.
+    header: Option<String>,
+}
+
+fn toml_value_to_json(value: &toml_edit::Value) -> Value {
+    use toml_edit::Value as T;
+    match value {
+        T::String(s) => s.value().clone().into(),
+        T::Integer(i) => (*i.value()).into(),
+        T::Float(f) => (*f.value()).into(),
+        T::Boolean(b) => (*b.value()).into(),
+        T::Datetime(d) => d.value().to_string().into(),
+        T::Array(a) => a.iter().map(toml_value_to_json).collect(),
+        T::InlineTable(t) => t
+            .iter()
+            .map(|(k, v)| (k.to_string(), toml_value_to_json(v)))
+            .collect::<Map<_, _>>()
+            .into(),
+    }
+}
+
+fn toml_to_json(item: &Item) -> Option<Value> {
+    match item {
+        Item::None => None,
+        Item::Value(value) => Some(toml_value_to_json(value)),
+        Item::Table(table) => Some(
+            table
+                .iter()
+                .filter_map(|(k, item)| Some((k.to_string(), toml_to_json(item)?)))
+                .collect::<Map<_, _>>()
+                .into(),
+        ),
+        Item::ArrayOfTables(tables) => Some(
+            tables
+                .iter()
+                .map(|t| toml_to_json(&Item::Table(t.clone())).unwrap_or_default())
+                .collect(),
+        ),
+    }
+}
+
+/// TOML has no null, so `null` fields are left out.
+fn json_to_toml(value: &Value) -> Option<toml_edit::Value> {
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64()?.into(),
+        },
+        Value::String(s) => s.as_str().into(),
+        Value::Array(items) => items
+            .iter()
+            .filter_map(json_to_toml)
+            .collect::<toml_edit::Array>()
+            .into(),
+        Value::Object(fields) => fields
+            .iter()
+            .filter_map(|(k, v)| Some((k.clone(), json_to_toml(v)?)))
+            .collect::<toml_edit::InlineTable>()
+            .into(),
+    })
+}
+
+impl TomlDocument {
+    fn get(&self, key: &str) -> Option<Value> {
+        self.doc.get(key).and_then(toml_to_json)
+    }
+
+    /// Update the fields of an existing table in place, so comments on
+    /// unchanged lines survive, or add a new table in key order.
+    fn put(&mut self, key: &str, fields: &Map<String, Value>) {
+        if let Some(table) = self
+            .doc
+            .get_mut(key)
+            .and_then(|item| item.as_table_like_mut())
+        {
+            let dropped: Vec<String> = table
+                .iter()
+                .map(|(k, _)| k.to_string())
+                .filter(|k| !fields.contains_key(k))
+                .collect();
+            for name in dropped {
+                table.remove(&name);
+            }
+            for (name, value) in ordered_fields(fields) {
+                let Some(mut new) = json_to_toml(value) else {
+                    table.remove(name);
+                    continue;
+                };
+                match table.get_mut(name) {
+                    Some(Item::Value(old)) if toml_value_to_json(old) == *value => {}
+                    Some(Item::Value(old)) => {
+                        *new.decor_mut() = old.decor().clone();
+                        *old = new;
+                    }
+                    _ => {
+                        table.insert(name, Item::Value(new));
+                    }
+                }
+            }
+            return;
+        }
+
+        let mut table = toml_edit::Table::new();
+        for (name, value) in ordered_fields(fields) {
+            if let Some(value) = json_to_toml(value) {
+                table.insert(name, Item::Value(value));
+            }
+        }
+        // Tables are written in position order, so make room for the new one.
+        let keys: Vec<&str> = self.doc.iter().map(|(k, _)| k).collect();
+        let next = insert_position(keys.iter().copied(), key)
+            .and_then(|i| self.doc.get(keys[i])?.as_table()?.position());
+        match next {
+            Some(position) => {
+                for (_, item) in self.doc.iter_mut() {
+                    if let Some(t) = item.as_table_mut()
+                        && let Some(p) = t.position().filter(|&p| p >= position)
+                    {
+                        t.set_position(p + 1);
+                    }
+                }
+                table.set_position(position);
+            }
+            None => {
+                let last = self
+                    .doc
+                    .iter()
+                    .filter_map(|(_, item)| item.as_table()?.position())
+                    .max()
+                    .unwrap_or(0);
+                table.set_position(last + 1);
+            }
+        }
+        self.doc.insert(key, Item::Table(table));
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        self.doc.remove(key).as_ref().and_then(toml_to_json)
+    }
+
+    fn render(&self) -> String {
+        let body = self.doc.to_string();
+        match &self.header {
+            Some(header) => format!("# {}\n\n{}", header, body.trim_start()),
+            None => body,
+        }
+    }
+}
+
+// ── YAML ───────────────────────────────────────────────────────
+
+/// A block-style YAML mapping, kept as the text of each entry.
+pub struct YamlDocument {
+    /// Lines before the first entry and its comments.
+    header: Vec<String>,
+    entries: Vec<YamlEntry>,
+    /// Blank lines and comments after the last entry.
+    footer: Vec<String>,
+    /// Indentation inside entries, matched by rewritten ones.
+    indent: usize,
+}
+
+struct YamlEntry {
+    key: String,
+    /// Blank lines and comments between the previous entry and this one.
+    leading: Vec<String>,
+    /// The `key:` line and its indented body.
+    lines: Vec<String>,
+}
+
+/// A single `key: entry` mapping, for rendering one entry.
+struct SingleEntry<'a>(&'a str, &'a Value);
+
+impl Serialize for SingleEntry<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(1))?;
+        map.serialize_entry(self.0, &OrderedFields(self.1))?;
+        map.end()
+    }
+}
+
+/// The comment at the end of a YAML line, with the space before it, if any.
+fn inline_comment(line: &str) -> Option<&str> {
+    let mut quote = None;
+    let mut prev = ' ';
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            // Only a quote that starts a scalar opens a string, not `it's`.
+            (None, '"' | '\'') if prev.is_whitespace() || prev == ':' => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if prev.is_whitespace() => {
+                let start = line[..i].trim_end().len();
+                return Some(&line[start..]);
+            }
+            _ => {}
+        }
+        prev = c;
+    }
+    None
+}
+
+/// Move the comments of an entry's old lines onto its re-rendered `new`
+/// lines: comment lines go back above the field they preceded, and end-of-line
+/// comments back onto their field (or the `key:` line). Comments of fields
+/// that were dropped end up after the last field.
+fn carry_comments(old: &[String], new: Vec<String>) -> Vec<String> {
+    let field = Regex::new(r"^\s+([\w-]+)\s*:").unwrap();
+    let name = |line: &str| field.captures(line).map(|caps| caps[1].to_string());
+
+    let mut above: Vec<(Option<String>, Vec<String>)> = Vec::new();
+    let mut inline: Vec<(Option<String>, String)> = Vec::new();
+    let mut comments = Vec::new();
+    for (i, line) in old.iter().enumerate() {
+        if i > 0 && line.trim_start().starts_with('#') {
+            comments.push(line.clone());
+            continue;
+        }
+        let owner = if i == 0 { None } else { name(line) };
+        if (i == 0 || owner.is_some())
+            && let Some(comment) = inline_comment(line)
+        {
+            inline.push((owner.clone(), comment.to_string()));
+        }
+        if owner.is_some() && !comments.is_empty() {
+            above.push((owner, std::mem::take(&mut comments)));
+        }
+    }
+
+    let new_names: Vec<Option<String>> = new
+        .iter()
+        .enumerate()
+        .map(|(i, line)| if i == 0 { None } else { name(line) })
+        .collect();
+    let kept = |owner: &Option<String>| owner.is_none() || new_names.contains(owner);
+    // Comments whose field is gone, and any after the last field.
+    let orphans: Vec<String> = above
+        .iter()
+        .filter(|(owner, _)| !kept(owner))
+        .flat_map(|(_, lines)| lines.iter().cloned())
+        .chain(comments)
+        .collect();
+
+    let mut lines = Vec::new();
+    for (i, (mut line, owner)) in new.into_iter().zip(&new_names).enumerate() {
+        if i > 0 && owner.is_none() {
+            lines.push(line);
+            continue;
+        }
+        if let Some((_, comments)) = above.iter().find(|(o, _)| o == owner && i > 0) {
+            lines.extend(comments.iter().cloned());
+        }
+        if let Some((_, comment)) = inline.iter().find(|(o, _)| o == owner) {
+            line.push_str(comment);
+        }
+        lines.push(line);
+    }
+    lines.extend(orphans);
+    lines
+}
+
+impl YamlDocument {
+    fn new(header: Option<String>) -> Self {
+        Self {
+            header: header
+                .map(|h| vec![format!("# {}", h), String::new()])
+                .unwrap_or_default(),
+            entries: Vec::new(),
+            footer: Vec::new(),
+            indent: 2,
+        }
+    }
+
+    fn parse(content: &str) -> AnyhowResult<Self> {
+        let key_line = Regex::new(r#"^("[^"]*"|'[^']*'|[^\s#'"\-\[{][^:#]*?)\s*:(\s|$)"#).unwrap();
+        let mut doc = Self::new(None);
+        // Lines not yet known to belong to an entry body.
+        let mut pending: Vec<String> = Vec::new();
+        for line in content.lines() {
+            if let Some(caps) = key_line.captures(line) {
+                let mut leading = std::mem::take(&mut pending);
+                if doc.entries.is_empty() {
+                    // Only the comments directly above the first entry are its own.
+                    let own = leading.len()
+                        - leading
+                            .iter()
+                            .rev()
+                            .take_while(|l| l.starts_with('#'))
+                            .count();
+                    doc.header = leading.drain(..own).collect();
+                }
+                doc.entries.push(YamlEntry {
+                    key: caps[1].trim_matches(['"', '\'']).to_string(),
+                    leading,
+                    lines: vec![line.to_string()],
+                });
+            } else if let Some(entry) = doc.entries.last_mut()
+                && line.starts_with([' ', '\t'])
+                && !line.trim().is_empty()
+            {
+                // Blank lines inside an entry's body stay with it.
+                entry.lines.append(&mut pending);
+                entry.lines.push(line.to_string());
+            } else {
+                pending.push(line.to_string());
+            }
+        }
+        if doc.entries.is_empty() {
+            doc.header = pending;
+        } else {
+            doc.footer = pending;
+        }
+        if let Some(line) = doc
+            .entries
+            .iter()
+            .flat_map(|e| e.lines.iter().skip(1))
+            .find(|l| l.starts_with(' ') && !l.trim().is_empty())
+        {
+            doc.indent = line.len() - line.trim_start_matches(' ').len();
+        }
+
+        let parsed: Vec<String> = parse_entries(Format::Yaml, content)?
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        let found: Vec<&String> = doc.entries.iter().map(|e| &e.key).collect();
+        anyhow::ensure!(
+            parsed.iter().eq(found),
+            "Only block-style YAML (one `key:` per line at the left margin) can be edited"
+        );
+        Ok(doc)
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.key == key)
+    }
+
+    fn get(&self, key: &str) -> Option<Value> {
+        let entry = &self.entries[self.position(key)?];
+        parse_entries(Format::Yaml, &entry.lines.join("\n"))
+            .ok()?
+            .into_iter()
+            .next()
+            .map(|(_, v)| v)
+    }
+
+    fn render_entry(&self, key: &str, fields: &Map<String, Value>) -> AnyhowResult<Vec<String>> {
+        let entry = Value::Object(fields.clone());
+        let yaml = serde_yaml_ng::to_string(&SingleEntry(key, &entry))?;
+        Ok(yaml
+            .lines()
+            .map(|line| {
+                let spaces = line.len() - line.trim_start_matches(' ').len();
+                format!(
+                    "{}{}",
+                    " ".repeat(spaces / 2 * self.indent),
+                    &line[spaces..]
+                )
+            })
+            .collect())
+    }
+
+    fn put(&mut self, key: &str, fields: &Map<String, Value>) -> AnyhowResult<()> {
+        let lines = self.render_entry(key, fields)?;
+        if let Some(i) = self.position(key) {
+            self.entries[i].lines = carry_comments(&self.entries[i].lines, lines);
+            return Ok(());
+        }
+
+        // Separate entries with a blank line if the file does.
+        let spaced = self
+            .entries
+            .iter()
+            .skip(1)
+            .any(|e| e.leading.first().is_some_and(|l| l.trim().is_empty()));
+        let blank = || {
+            if spaced {
+                vec![String::new()]
+            } else {
+                Vec::new()
+            }
+        };
+        let at = insert_position(self.entries.iter().map(|e| e.key.as_str()), key)
+            .unwrap_or(self.entries.len());
+        let leading = if at == 0 {
+            if let Some(first) = self.entries.first_mut() {
+                first.leading.splice(0..0, blank());
+            }
+            Vec::new()
+        } else {
+            blank()
+        };
+        self.entries.insert(
+            at,
+            YamlEntry {
+                key: key.to_string(),
+                leading,
+                lines,
+            },
+        );
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Value> {
+        let value = self.get(key)?;
+        let i = self.position(key)?;
+        self.entries.remove(i);
+        // The header already ends in any gap before the first entry.
+        if i == 0
+            && let Some(next) = self.entries.first_mut()
+        {
+            let gap = next
+                .leading
+                .iter()
+                .take_while(|l| l.trim().is_empty())
+                .count();
+            next.leading.drain(..gap);
+        }
+        Some(value)
+    }
+
+    fn render(&self) -> String {
+        let mut lines: Vec<&str> = self.header.iter().map(String::as_str).collect();
+        for entry in &self.entries {
+            lines.extend(entry.leading.iter().map(String::as_str));
+            lines.extend(entry.lines.iter().map(String::as_str));
+        }
+        lines.extend(self.footer.iter().map(String::as_str));
+        let mut text = lines.join("\n");
+        text.push('\n');
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    fn edit(format: Format, content: &str, change: impl FnOnce(&mut Document)) -> String {
+        let mut doc = Document::parse(format, content).unwrap();
+        change(&mut doc);
+        doc.render().unwrap()
+    }
+
+    const YAML: &str = "\
+# Team overrides
+
+# Shop moved in 2024
+frc254:  # Cheesy Poofs
+  # From the team's site
+  lat: 37.25
+  lng: -121.96  # approximate
+  reason: it's the shop
+  # Checked again in 2025
+
+frc1678:
+  lat: 38.54
+  lng: -121.74
+";
+
+    #[test]
+    fn yaml_renders_unchanged_file_as_is() {
+        assert_eq!(edit(Format::Yaml, YAML, |_| {}), YAML);
+    }
+
+    #[test]
+    fn yaml_keeps_comments_in_a_rewritten_entry() {
+        let rendered = edit(Format::Yaml, YAML, |doc| {
+            let entry = serde_json::json!({"lat": 37.5, "lng": -121.96, "reason": "it's the shop"});
+            doc.put("frc254", fields(entry)).unwrap();
+        });
+        let want = YAML.replace("lat: 37.25", "lat: 37.5");
+        assert_eq!(rendered, want);
+
+        // A dropped field's comment moves after the remaining fields.
+        let rendered = edit(Format::Yaml, YAML, |doc| {
+            doc.put("frc254", fields(serde_json::json!({"ignore": true})))
+                .unwrap();
+        });
+        assert!(rendered.contains(
+            "frc254:  # Cheesy Poofs\n  ignore: true\n  # From the team's site\n  # Checked again in 2025\n"
+        ));
+    }
+
+    #[test]
+    fn yaml_inserts_and_removes_in_key_order() {
+        let rendered = edit(Format::Yaml, YAML, |doc| {
+            doc.put(
+                "frc971",
+                fields(serde_json::json!({"lat": 37.4, "lng": -122.0})),
+            )
+            .unwrap();
+        });
+        assert!(rendered.contains(
+            "  # Checked again in 2025\n\nfrc971:\n  lat: 37.4\n  lng: -122.0\n\nfrc1678:\n"
+        ));
+
+        let mut doc = Document::parse(Format::Yaml, &rendered).unwrap();
+        assert_eq!(
+            doc.get("frc971"),
+            Some(serde_json::json!({"lat": 37.4, "lng": -122.0}))
+        );
+        doc.remove("frc971").unwrap();
+        assert_eq!(doc.render().unwrap(), YAML);
+    }
+
+    const TOML: &str = "\
+# Team overrides
+
+# Shop moved in 2024
+[frc254]
+lat = 37.25  # from the team's site
+lng = -121.96
+
+[frc1678]
+lat = 38.54
+lng = -121.74
+";
+
+    #[test]
+    fn toml_renders_unchanged_file_as_is() {
+        assert_eq!(edit(Format::Toml, TOML, |_| {}), TOML);
+    }
+
+    #[test]
+    fn toml_edits_in_place_and_in_key_order() {
+        let rendered = edit(Format::Toml, TOML, |doc| {
+            doc.put(
+                "frc254",
+                fields(serde_json::json!({"lat": 37.5, "lng": -121.96})),
+            )
+            .unwrap();
+            doc.put(
+                "frc971",
+                fields(serde_json::json!({"lat": 37.4, "lng": -122.0})),
+            )
+            .unwrap();
+        });
+        let want = TOML.replace("lat = 37.25", "lat = 37.5").replace(
+            "[frc1678]",
+            "[frc971]\nlat = 37.4\nlng = -122.0\n\n[frc1678]",
+        );
+        assert_eq!(rendered, want);
+
+        let mut doc = Document::parse(Format::Toml, &rendered).unwrap();
+        assert_eq!(
+            doc.get("frc971"),
+            Some(serde_json::json!({"lat": 37.4, "lng": -122.0}))
+        );
+        doc.remove("frc971").unwrap();
+        doc.put(
+            "frc254",
+            fields(serde_json::json!({"lat": 37.25, "lng": -121.96})),
+        )
+        .unwrap();
+        assert_eq!(doc.render().unwrap(), TOML);
+    }
+}
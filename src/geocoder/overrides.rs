@@ -0,0 +1,398 @@
+//! Editing of the manual location override files (`locations/teams.json`,
+//! `locations/events.json`, or their TOML/YAML equivalents).
+//!
+//! Existing entries keep their order and `_comment` stays first; new keys are
+//! inserted in natural order (`frc254` before `frc1114`), like `bisect.insort`.
+//! Extra fields on an entry are preserved. See `override_formats` for how
+//! each file format is read and written.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result as AnyhowResult};
+use log::warn;
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{LocationKind, OverrideAction},
+    fsio, geo,
+    geocoder::{
+        archive, coords,
+        gazetteer::Gazetteer,
+        layout::natural_cmp,
+        load_location_file,
+        override_formats::{Document, Format},
+        resolve::LocationSource,
+        types::{LocationDict, LocationOverride},
+    },
+};
+
+/// Fields an override entry understands, in the order they are written.
+/// Any other field follows them.
+pub(crate) const OVERRIDE_FIELDS: &[&str] = &[
+    "lat",
+    "lng",
+    "ignore",
+    "valid_from",
+    "valid_until",
+    "reason",
+    "source",
+    "added",
+];
+
+/// An override this close to where another source placed its key adds
+/// nothing, in meters.
+const SAME_LOCATION_M: f64 = 100.0;
+
+/// Normalize a team or event key: lowercase, and `254` becomes `frc254` for teams.
+pub fn normalize_key(raw: &str, kind: LocationKind) -> String {
+    let key = raw.trim().to_lowercase();
+    if kind == LocationKind::Teams && !key.is_empty() && key.chars().all(|c| c.is_ascii_digit()) {
+        return format!("frc{}", key);
+    }
+    key
+}
+
+/// An override file loaded for editing, in the format its extension names.
+pub struct OverrideFile {
+    path: PathBuf,
+    doc: Document,
+}
+
+impl OverrideFile {
+    /// An empty override file that will be written to `path`.
+    pub fn new(path: &Path, comment: Option<Value>) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            doc: Document::new(Format::of(path), comment),
+        }
+    }
+
+    pub fn load(path: &Path) -> AnyhowResult<Self> {
+        let format = Format::of(path);
+        let doc = if path.exists() {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            Document::parse(format, &content)
+                .with_context(|| format!("Failed to parse {} as {}", path.display(), format))?
+        } else {
+            Document::new(format, None)
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            doc,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.doc.get(key)
+    }
+
+    /// Edit the fields of the entry for `key`, creating it if needed. An
+    /// entry left with no fields is removed.
+    pub fn update(
+        &mut self,
+        key: &str,
+        edit: impl FnOnce(&mut Map<String, Value>),
+    ) -> AnyhowResult<()> {
+        let mut fields = match self.doc.get(key) {
+            Some(Value::Object(fields)) => fields,
+            Some(_) => anyhow::bail!("Entry for {} is not an object", key),
+            None => Map::new(),
+        };
+        edit(&mut fields);
+        if fields.is_empty() {
+            self.doc.remove(key);
+            Ok(())
+        } else {
+            self.doc.put(key, fields)
+        }
+    }
+
+    pub fn set_location(&mut self, key: &str, lat: f64, lng: f64) -> AnyhowResult<()> {
+        self.update(key, |entry| {
+            entry.insert("lat".to_string(), lat.into());
+            entry.insert("lng".to_string(), lng.into());
+        })
+    }
+
+    /// Set the ignore flag. Clearing it drops the field, and the entry too if
+    /// nothing else is left in it.
+    pub fn set_ignore(&mut self, key: &str, ignore: bool) -> AnyhowResult<()> {
+        self.update(key, |entry| {
+            if ignore {
+                entry.insert("ignore".to_string(), true.into());
+            } else {
+                entry.remove("ignore");
+            }
+        })
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.doc.remove(key)
+    }
+
+    pub fn save(&self) -> AnyhowResult<()> {
+        fsio::write_atomic(&self.path, self.doc.render()?)
+    }
+}
+
+/// Why an override looks stale, if it does: it expired before `year`, the
+/// newest season output placed its key from another source (at the same spot
+/// or elsewhere), or its key isn't in any of the `recent` season outputs.
+/// Overrides that only start applying in a later season are not stale.
+fn stale_reason(
+    key: &str,
+    ov: &LocationOverride,
+    year: u32,
+    by_season: &BTreeMap<i64, Map<String, Value>>,
+    recent: &BTreeSet<&String>,
+) -> Option<String> {
+    if let Some(until) = ov.valid_until.filter(|&until| until < year) {
+        return Some(format!("expired after {}", until));
+    }
+    if !ov.applies_to(year) {
+        return None;
+    }
+    let last_seen = by_season
+        .iter()
+        .rev()
+        .find_map(|(season, objects)| Some((*season, objects.get(key)?)));
+    if let (Some((season, output)), Some(lat), Some(lng)) = (last_seen, ov.lat, ov.lng)
+        && ov.applies_to(season as u32)
+    {
+        let source = output.get("location_source").and_then(Value::as_str);
+        let placed = output
+            .get("lat")
+            .and_then(Value::as_f64)
+            .zip(output.get("lng").and_then(Value::as_f64));
+        if let (Some(source), Some((placed_lat, placed_lng))) = (source, placed)
+            && source != LocationSource::Override.name()
+        {
+            return Some(
+                if geo::distance_m(lat, lng, placed_lat, placed_lng) <= SAME_LOCATION_M {
+                    format!("matches the {} location in {}", source, season)
+                } else {
+                    format!("superseded by {} in {}", source, season)
+                },
+            );
+        }
+    }
+    if by_season.is_empty() || recent.iter().any(|k| *k == key) {
+        return None;
+    }
+    Some(match last_seen {
+        Some((season, _)) => format!("not needed since {}", season),
+        None => "not in any season".to_string(),
+    })
+}
+
+/// Print the overrides of `kind` that have expired, were bypassed in the
+/// newest season output, or haven't been needed in the last `seasons` season
+/// outputs.
+fn report_stale(
+    overrides: &LocationDict,
+    kind: LocationKind,
+    output_path: &Path,
+    year: u32,
+    seasons: u32,
+) -> AnyhowResult<usize> {
+    let by_season = archive::season_objects(output_path, kind)?;
+    if by_season.is_empty() {
+        warn!(
+            "No season output files in {}; only expired overrides are reported",
+            output_path.display()
+        );
+    }
+    let recent: BTreeSet<&String> = by_season
+        .values()
+        .rev()
+        .take(seasons as usize)
+        .flat_map(|objects| objects.keys())
+        .collect();
+
+    let mut keys: Vec<&String> = overrides.keys().collect();
+    keys.sort_by(|a, b| natural_cmp(a, b));
+    let mut count = 0;
+    for key in keys {
+        let ov = &overrides[key];
+        if let Some(why) = stale_reason(key, ov, year, &by_season, &recent) {
+            match &ov.reason {
+                Some(reason) => println!("{}: {} ({})", key, why, reason),
+                None => println!("{}: {}", key, why),
+            }
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Handle the `overrides` subcommand. `year` is the current season, for the
+/// stale report.
+pub fn run_command(
+    teams_path: &Path,
+    events_path: &Path,
+    gazetteer_path: &Path,
+    output_path: &Path,
+    year: u32,
+    action: &OverrideAction,
+) -> AnyhowResult<()> {
+    let path_for = |kind: LocationKind| match kind {
+        LocationKind::Teams => teams_path,
+        LocationKind::Events => events_path,
+    };
+    match action {
+        OverrideAction::Add {
+            kind,
+            key,
+            coords,
+            valid_from,
+            valid_until,
+            reason,
+            source,
+        } => {
+            if let (Some(from), Some(until)) = (valid_from, valid_until) {
+                anyhow::ensure!(from <= until, "--valid-from is after --valid-until");
+            }
+            let key = normalize_key(key, *kind);
+            let (lat, lng) = coords::parse_location(&coords.join(" "), |locality| {
+                let hit = Gazetteer::load(gazetteer_path)
+                    .lookup_text(locality)
+                    .with_context(|| format!("'{}' is not in the gazetteer", locality))?;
+                Ok((hit.lat, hit.lng))
+            })?;
+            let mut file = OverrideFile::load(path_for(*kind))?;
+            let previous = file.get(&key);
+            let today = chrono::Utc::now().date_naive();
+            let fields = [
+                ("lat", Some(Value::from(lat))),
+                ("lng", Some(Value::from(lng))),
+                ("valid_from", valid_from.map(Value::from)),
+                ("valid_until", valid_until.map(Value::from)),
+                ("reason", reason.clone().map(Value::from)),
+                ("source", source.clone().map(Value::from)),
+                (
+                    "added",
+                    previous.is_none().then(|| today.to_string().into()),
+                ),
+            ];
+            // One update, so a new entry is written with its fields in order.
+            file.update(&key, |entry| {
+                for (field, value) in fields {
+                    if let Some(value) = value {
+                        entry.insert(field.to_string(), value);
+                    }
+                }
+            })?;
+            file.save()?;
+            match previous {
+                Some(prev) => println!("Updated {}: {} -> ({}, {})", key, prev, lat, lng),
+                None => println!("Added {}: ({}, {})", key, lat, lng),
+            }
+        }
+        OverrideAction::Remove { kind, key } => {
+            let key = normalize_key(key, *kind);
+            let mut file = OverrideFile::load(path_for(*kind))?;
+            let removed = file.remove(&key);
+            anyhow::ensure!(removed.is_some(), "No override for {}", key);
+            file.save()?;
+            println!("Removed {}", key);
+        }
+        OverrideAction::SetIgnore { kind, key, ignore } => {
+            let key = normalize_key(key, *kind);
+            let mut file = OverrideFile::load(path_for(*kind))?;
+            file.set_ignore(&key, *ignore)?;
+            file.save()?;
+            println!("Set ignore = {} for {}", ignore, key);
+        }
+        OverrideAction::Stale { kind, seasons } => {
+            anyhow::ensure!(*seasons > 0, "--seasons must be at least 1");
+            let kinds = match kind {
+                Some(kind) => vec![*kind],
+                None => vec![LocationKind::Teams, LocationKind::Events],
+            };
+            let mut count = 0;
+            for kind in kinds {
+                let path = path_for(kind);
+                if !path.is_file() {
+                    continue;
+                }
+                let overrides = load_location_file(path)
+                    .with_context(|| format!("Failed to load {}", path.display()))?;
+                count += report_stale(&overrides, kind, output_path, year, *seasons)?;
+            }
+            println!("{} stale overrides", count);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// `stale_reason` for `key`, counting the newest `seasons` outputs as recent.
+    fn stale(key: &str, ov: Value, year: u32, outputs: Value, seasons: usize) -> Option<String> {
+        let ov: LocationOverride = serde_json::from_value(ov).unwrap();
+        let by_season: BTreeMap<i64, Map<String, Value>> = serde_json::from_value(outputs).unwrap();
+        let recent = by_season
+            .values()
+            .rev()
+            .take(seasons)
+            .flat_map(|objects| objects.keys())
+            .collect();
+        stale_reason(key, &ov, year, &by_season, &recent)
+    }
+
+    #[test]
+    fn compares_overrides_with_the_season_output() {
+        let ov = json!({"lat": 37.25, "lng": -121.96});
+        let placed = |source: &str, lat: f64| json!({"2024": {"frc254": {"lat": lat, "lng": -121.96, "location_source": source}}});
+
+        // The override won, so it is still doing its job.
+        assert_eq!(
+            stale("frc254", ov.clone(), 2024, placed("override", 37.25), 3),
+            None
+        );
+        // Another source placed the team within a few meters anyway.
+        assert_eq!(
+            stale("frc254", ov.clone(), 2024, placed("tba", 37.2504), 3).as_deref(),
+            Some("matches the tba location in 2024")
+        );
+        // Another source won with a different location.
+        assert_eq!(
+            stale("frc254", ov, 2024, placed("archive", 37.5), 3).as_deref(),
+            Some("superseded by archive in 2024")
+        );
+        // A season the override didn't cover says nothing about it.
+        let from_2025 = json!({"lat": 37.25, "lng": -121.96, "valid_from": 2025});
+        assert_eq!(
+            stale("frc254", from_2025, 2025, placed("tba", 37.5), 3),
+            None
+        );
+    }
+
+    #[test]
+    fn reports_expired_and_unused_overrides() {
+        let outputs = json!({"2022": {"frc254": {}}, "2024": {"frc1678": {}}});
+        let ov = json!({"lat": 37.25, "lng": -121.96});
+        let expiring = json!({"lat": 37.25, "lng": -121.96, "valid_until": 2023});
+
+        assert_eq!(
+            stale("frc254", expiring, 2024, outputs.clone(), 3).as_deref(),
+            Some("expired after 2023")
+        );
+        assert_eq!(stale("frc254", ov.clone(), 2024, outputs.clone(), 2), None);
+        assert_eq!(
+            stale("frc254", ov.clone(), 2024, outputs.clone(), 1).as_deref(),
+            Some("not needed since 2022")
+        );
+        assert_eq!(
+            stale("frc9999", ov, 2024, outputs, 1).as_deref(),
+            Some("not in any season")
+        );
+    }
+}
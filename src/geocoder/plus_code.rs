@@ -0,0 +1,173 @@
+//! Offline decoding of Open Location Codes ("Plus Codes").
+//!
+//! Follows the reference algorithm at
+//! <https://github.com/google/open-location-code/blob/main/Documentation/Specification/olc_definition.adoc>.
+
+use anyhow::Result as AnyhowResult;
+
+const ALPHABET: &[u8] = b"23456789CFGHJMPQRVWX";
+const SEPARATOR: char = '+';
+const SEPARATOR_POSITION: usize = 8;
+const PAIR_CODE_LENGTH: usize = 10;
+const GRID_ROWS: f64 = 5.0;
+const GRID_COLUMNS: f64 = 4.0;
+
+fn digit_value(c: char) -> Option<f64> {
+    ALPHABET
+        .iter()
+        .position(|&d| d as char == c.to_ascii_uppercase())
+        .map(|v| v as f64)
+}
+
+/// Place value, in degrees, of the `i`th digit pair (20°, 1°, 0.05°, ...).
+fn pair_resolution(i: usize) -> f64 {
+    20f64.powi(1 - i as i32)
+}
+
+/// Whether `text` looks like a Plus Code, full or short.
+pub fn is_plus_code(text: &str) -> bool {
+    let Some(sep) = text.find(SEPARATOR) else {
+        return false;
+    };
+    sep <= SEPARATOR_POSITION
+        && sep.is_multiple_of(2)
+        && text.chars().filter(|&c| c == SEPARATOR).count() == 1
+        && text
+            .chars()
+            .all(|c| c == SEPARATOR || c == '0' || digit_value(c).is_some())
+}
+
+/// Whether a Plus Code is a short code that needs a reference location.
+pub fn is_short(code: &str) -> bool {
+    code.find(SEPARATOR)
+        .is_some_and(|sep| sep < SEPARATOR_POSITION)
+}
+
+/// Decode a full Plus Code to the center of its area.
+pub fn decode(code: &str) -> AnyhowResult<(f64, f64)> {
+    anyhow::ensure!(is_plus_code(code), "'{}' is not a Plus Code", code);
+    anyhow::ensure!(
+        !is_short(code),
+        "'{}' is a short Plus Code and needs a reference location",
+        code
+    );
+    let digits: Vec<char> = code
+        .chars()
+        .filter(|&c| c != SEPARATOR)
+        .take_while(|&c| c != '0')
+        .collect();
+    anyhow::ensure!(
+        (digits.len() >= 2 && digits.len().is_multiple_of(2)) || digits.len() > PAIR_CODE_LENGTH,
+        "'{}' has an invalid number of digits",
+        code
+    );
+
+    let (mut lat, mut lng) = (-90.0, -180.0);
+    let (mut lat_res, mut lng_res) = (0.0, 0.0);
+    for (i, pair) in digits[..digits.len().min(PAIR_CODE_LENGTH)]
+        .chunks(2)
+        .enumerate()
+    {
+        let res = pair_resolution(i);
+        lat += digit_value(pair[0]).unwrap() * res;
+        lng += digit_value(pair[1]).unwrap() * res;
+        (lat_res, lng_res) = (res, res);
+    }
+    // Digits past the tenth refine a 5x4 grid.
+    for &c in digits.iter().skip(PAIR_CODE_LENGTH) {
+        let value = digit_value(c).unwrap();
+        lat_res /= GRID_ROWS;
+        lng_res /= GRID_COLUMNS;
+        lat += (value / GRID_COLUMNS).floor() * lat_res;
+        lng += (value % GRID_COLUMNS) * lng_res;
+    }
+    anyhow::ensure!(lat < 90.0 && lng < 180.0, "'{}' is out of range", code);
+    Ok((lat + lat_res / 2.0, lng + lng_res / 2.0))
+}
+
+/// The first `len` digits of the code for a location, `len` even and <= 8.
+fn encode_prefix(lat: f64, lng: f64, len: usize) -> String {
+    let mut lat = (lat.clamp(-90.0, 90.0 - 1e-9)) + 90.0;
+    let mut lng = (lng + 180.0).rem_euclid(360.0);
+    let mut code = String::new();
+    for i in 0..len / 2 {
+        let res = pair_resolution(i);
+        let (dlat, dlng) = ((lat / res).floor(), (lng / res).floor());
+        lat -= dlat * res;
+        lng -= dlng * res;
+        code.push(ALPHABET[dlat as usize] as char);
+        code.push(ALPHABET[dlng as usize] as char);
+    }
+    code
+}
+
+/// Recover a short Plus Code to the full area nearest `reference`, then decode it.
+pub fn decode_short(code: &str, reference: (f64, f64)) -> AnyhowResult<(f64, f64)> {
+    anyhow::ensure!(is_plus_code(code), "'{}' is not a Plus Code", code);
+    if !is_short(code) {
+        return decode(code);
+    }
+    let padding = SEPARATOR_POSITION - code.find(SEPARATOR).unwrap();
+    let resolution = 20f64.powi(2 - (padding / 2) as i32);
+    let half = resolution / 2.0;
+    let (ref_lat, ref_lng) = reference;
+
+    let full = format!("{}{}", encode_prefix(ref_lat, ref_lng, padding), code);
+    let (mut lat, mut lng) = decode(&full)?;
+
+    // The prefix picks the cell containing the reference; the nearest match
+    // may be in a neighbouring cell.
+    if ref_lat + half < lat && lat - resolution >= -90.0 {
+        lat -= resolution;
+    } else if ref_lat - half > lat && lat + resolution <= 90.0 {
+        lat += resolution;
+    }
+    if ref_lng + half < lng {
+        lng -= resolution;
+    } else if ref_lng - half > lng {
+        lng += resolution;
+    }
+    if lng > 180.0 {
+        lng -= 360.0;
+    } else if lng < -180.0 {
+        lng += 360.0;
+    }
+    Ok((lat, lng))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near((lat, lng): (f64, f64), (want_lat, want_lng): (f64, f64)) {
+        assert!(
+            (lat - want_lat).abs() < 1e-9 && (lng - want_lng).abs() < 1e-9,
+            "got ({}, {}), want ({}, {})",
+            lat,
+            lng,
+            want_lat,
+            want_lng
+        );
+    }
+
+    #[test]
+    fn decodes_full_codes() {
+        assert_near(decode("849VCWC8+R9").unwrap(), (37.4220625, -122.0840625));
+        assert_near(decode("8FVC0000+").unwrap(), (47.5, 8.5));
+        assert_near(decode("9C3W9QCJ+2VX").unwrap(), (51.3701125, -1.217765625));
+        assert!(decode("CWC8+R9").is_err());
+        assert!(decode("849VCWC8+R9A1").is_err());
+    }
+
+    #[test]
+    fn recovers_short_codes() {
+        let want = (37.4220625, -122.0840625);
+        assert_near(decode_short("CWC8+R9", (37.4, -122.1)).unwrap(), want);
+        // The nearest match is in the cell north of the reference's.
+        assert_near(decode_short("CWC8+R9", (36.99, -122.08)).unwrap(), want);
+        assert_near(
+            decode_short("CJ+2VX", (51.3708675, -1.217765625)).unwrap(),
+            (51.3701125, -1.217765625),
+        );
+    }
+}
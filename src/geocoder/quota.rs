@@ -0,0 +1,198 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result as AnyhowResult;
+use chrono::{Duration, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::GeocodingSettings, fsio};
+
+const LEDGER_FILE: &str = "geocode_quota.json";
+
+/// Number of days of history kept in the ledger file.
+const LEDGER_RETENTION_DAYS: i64 = 31;
+
+/// On-disk format of the quota ledger: paid geocode calls per UTC day.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LedgerFile {
+    #[serde(default)]
+    days: BTreeMap<String, u32>,
+}
+
+/// Tracks paid geocode calls against the per-run and per-day budgets.
+/// Persisted next to the location archive so the daily count survives
+/// across runs.
+pub struct QuotaLedger {
+    path: PathBuf,
+    days: BTreeMap<String, u32>,
+    used_this_run: u32,
+    max_per_run: Option<u32>,
+    max_per_day: Option<u32>,
+}
+
+impl QuotaLedger {
+    pub fn load(archive_path: &Path, settings: &GeocodingSettings) -> Self {
+        let path = archive_path.join(LEDGER_FILE);
+        let days = match std::fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<LedgerFile>(&content) {
+                Ok(ledger) => ledger.days,
+                Err(e) => {
+                    warn!("Failed to parse geocode quota ledger: {}", e);
+                    BTreeMap::new()
+                }
+            },
+            Err(_) => BTreeMap::new(),
+        };
+        Self {
+            path,
+            days,
+            used_this_run: 0,
+            max_per_run: settings.max_calls_per_run,
+            max_per_day: settings.max_calls_per_day,
+        }
+    }
+
+    fn today() -> String {
+        Utc::now().date_naive().to_string()
+    }
+
+    /// Paid calls recorded for the current UTC day.
+    pub fn used_today(&self) -> u32 {
+        self.days.get(&Self::today()).copied().unwrap_or(0)
+    }
+
+    /// Reserve one paid call. Returns `false` if either budget is used up.
+    pub fn try_acquire(&mut self) -> bool {
+        if self
+            .max_per_run
+            .is_some_and(|max| self.used_this_run >= max)
+        {
+            return false;
+        }
+        if self.max_per_day.is_some_and(|max| self.used_today() >= max) {
+            return false;
+        }
+        self.used_this_run += 1;
+        *self.days.entry(Self::today()).or_default() += 1;
+        true
+    }
+
+    /// Give back a reserved call that turned out not to be billed
+    /// (e.g. it was served from the HTTP cache).
+    pub fn release(&mut self) {
+        self.used_this_run = self.used_this_run.saturating_sub(1);
+        if let Some(count) = self.days.get_mut(&Self::today()) {
+            *count = count.saturating_sub(1);
+        }
+    }
+
+    /// Write the ledger back to disk, dropping days past the retention window.
+    pub fn save(&mut self) -> AnyhowResult<()> {
+        let cutoff = (Utc::now() - Duration::days(LEDGER_RETENTION_DAYS))
+            .date_naive()
+            .to_string();
+        self.days.retain(|day, _| *day >= cutoff);
+
+        let ledger = LedgerFile {
+            days: self.days.clone(),
+        };
+        let json = serde_json::to_string_pretty(&ledger)?;
+        fsio::write_atomic(&self.path, json)?;
+        info!(
+            "Paid geocode calls: {} this run, {} today",
+            self.used_this_run,
+            self.used_today()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(
+        max_calls_per_run: Option<u32>,
+        max_calls_per_day: Option<u32>,
+    ) -> GeocodingSettings {
+        GeocodingSettings {
+            max_calls_per_run,
+            max_calls_per_day,
+            ..GeocodingSettings::default()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("frcmap-quota-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn stops_when_a_budget_is_used_up() {
+        let dir = temp_dir("budget");
+        let mut ledger = QuotaLedger::load(&dir, &settings(Some(2), None));
+        assert!(ledger.try_acquire());
+        assert!(ledger.try_acquire());
+        assert!(!ledger.try_acquire());
+        assert_eq!(ledger.used_today(), 2);
+
+        let mut ledger = QuotaLedger::load(&dir, &settings(None, Some(1)));
+        assert!(ledger.try_acquire());
+        assert!(!ledger.try_acquire());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn release_gives_back_a_cached_call() {
+        let dir = temp_dir("release");
+        let mut ledger = QuotaLedger::load(&dir, &settings(Some(1), Some(1)));
+        assert!(ledger.try_acquire());
+        // Served from the HTTP cache (an x-cache HIT), so not billed.
+        ledger.release();
+        assert_eq!(ledger.used_today(), 0);
+        assert!(ledger.try_acquire());
+        assert!(!ledger.try_acquire());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn daily_count_survives_reload_and_resets_the_next_day() {
+        let dir = temp_dir("reload");
+        let day = |days_ago: i64| {
+            (Utc::now() - Duration::days(days_ago))
+                .date_naive()
+                .to_string()
+        };
+        let ledger = LedgerFile {
+            days: BTreeMap::from([(day(1), 5), (day(LEDGER_RETENTION_DAYS + 1), 7)]),
+        };
+        std::fs::write(
+            dir.join(LEDGER_FILE),
+            serde_json::to_string(&ledger).unwrap(),
+        )
+        .unwrap();
+
+        // Yesterday's calls don't count against today's budget.
+        let mut ledger = QuotaLedger::load(&dir, &settings(None, Some(2)));
+        assert_eq!(ledger.used_today(), 0);
+        assert!(ledger.try_acquire());
+        ledger.save().unwrap();
+
+        // Today's calls do, across runs; the per-run count starts over.
+        let mut ledger = QuotaLedger::load(&dir, &settings(Some(1), Some(2)));
+        assert_eq!(ledger.used_today(), 1);
+        assert!(ledger.try_acquire());
+        assert!(!ledger.try_acquire());
+
+        // Days past the retention window are dropped on save.
+        let saved: LedgerFile =
+            serde_json::from_str(&std::fs::read_to_string(dir.join(LEDGER_FILE)).unwrap()).unwrap();
+        assert_eq!(saved.days.keys().collect::<Vec<_>>(), [&day(1), &day(0)]);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
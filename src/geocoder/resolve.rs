@@ -0,0 +1,474 @@
+use std::collections::{HashMap, HashSet};
+
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    geocoder::{
+        FrcGeocoder, apply_override,
+        archive::{Archive, ArchiveEntry, ArchiveInvalidation, VenueRegistry},
+        external::ExternalSource,
+        gazetteer::GazetteerPrecision,
+        normalize,
+        types::{GeocodeMatch, GeocodeQuality, LocationDict, Provenance},
+    },
+    map_types::HasLocation,
+};
+
+/// One step of the location resolution chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocationSource {
+    /// Manual override file (`locations/teams.json`, `locations/events.json`).
+    Override,
+    /// Third-party override sources from the `[external]` settings, in
+    /// priority order.
+    External,
+    /// Location archive from a previous run.
+    Archive,
+    /// Venue registry: a location from an earlier event at the same venue
+    /// (events only).
+    Venue,
+    /// `lat`/`lng` supplied by TBA itself.
+    Tba,
+    /// Google Place Details lookup of TBA's `gmaps_place_id`.
+    PlaceId,
+    /// Venue/address from the FIRST API, then geocoded (events only).
+    FirstApi,
+    /// Free-text geocoding of the TBA address.
+    Geocode,
+    /// Offline postal-code/city centroid from the GeoNames gazetteer.
+    Gazetteer,
+}
+
+impl LocationSource {
+    /// Name used in settings and output files.
+    pub fn name(self) -> &'static str {
+        match self {
+            LocationSource::Override => "override",
+            LocationSource::External => "external",
+            LocationSource::Archive => "archive",
+            LocationSource::Venue => "venue",
+            LocationSource::Tba => "tba",
+            LocationSource::PlaceId => "place_id",
+            LocationSource::FirstApi => "first_api",
+            LocationSource::Geocode => "geocode",
+            LocationSource::Gazetteer => "gazetteer",
+        }
+    }
+
+    /// Whether this source may spend paid API calls.
+    fn is_paid(self) -> bool {
+        matches!(
+            self,
+            LocationSource::PlaceId | LocationSource::FirstApi | LocationSource::Geocode
+        )
+    }
+}
+
+/// Inputs shared by every object in one resolution pass.
+pub(super) struct ResolveContext<'a> {
+    pub year: u32,
+    pub chain: &'a [LocationSource],
+    pub overrides: &'a LocationDict,
+    pub external: &'a [ExternalSource],
+    pub archive: &'a Archive,
+    pub venues: &'a VenueRegistry,
+}
+
+/// Per-object state carried between steps of the chain.
+struct Attempt {
+    /// Location TBA supplied before the chain started.
+    tba: Option<(f64, f64)>,
+    /// The object's address before any step (e.g. the FIRST API) changed it.
+    /// Provenance records this one, so the next run hashes the same string.
+    address: Option<String>,
+    /// Addresses already sent to the geocoder, so a later step doesn't repeat them.
+    geocoded: HashSet<String>,
+}
+
+impl FrcGeocoder {
+    /// Run the resolution chain over every object. Each object takes the
+    /// location from the first source that yields one; an object marked as
+    /// ignored stops the chain.
+    pub(super) async fn resolve_locations<T: HasLocation>(
+        &self,
+        objects: &mut HashMap<String, T>,
+        ctx: &ResolveContext<'_>,
+    ) {
+        let mut keys: Vec<String> = objects.keys().cloned().collect();
+        keys.sort();
+        for key in &keys {
+            let obj = objects.get_mut(key).unwrap();
+            let mut attempt = Attempt {
+                tba: obj.lat().zip(obj.lng()),
+                address: obj.address(),
+                geocoded: HashSet::new(),
+            };
+            obj.clear_location();
+
+            for &source in ctx.chain {
+                if source.is_paid() && !obj.allow_paid_lookup() {
+                    debug!("Skipping {:?} for {}: not eligible", source, key);
+                    continue;
+                }
+                if self.try_source(source, key, obj, ctx, &mut attempt).await {
+                    debug!("{} located via {:?}", key, source);
+                    let provenance = obj.provenance();
+                    let resolved_by = provenance.map(|p| p.source);
+                    let accuracy = provenance
+                        .and_then(|p| p.quality.as_ref())
+                        .and_then(|q| q.accuracy_m());
+                    obj.set_location_source(source, resolved_by, accuracy);
+                    break;
+                }
+                if obj.is_ignored() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Try a single source. Returns `true` once the object has a location.
+    async fn try_source<T: HasLocation>(
+        &self,
+        source: LocationSource,
+        key: &str,
+        obj: &mut T,
+        ctx: &ResolveContext<'_>,
+        attempt: &mut Attempt,
+    ) -> bool {
+        match source {
+            LocationSource::Override => {
+                if let Some(ov) = ctx.overrides.get(key) {
+                    if !apply_override(obj, ov, ctx.year) {
+                        info!("Override for {} does not apply to {}", key, ctx.year);
+                        return false;
+                    }
+                    if obj.has_location() {
+                        obj.set_provenance(Provenance::resolved(source, attempt.address.clone()));
+                    }
+                }
+            }
+            LocationSource::External => {
+                // Only coordinates are taken from third parties; ignoring a
+                // team or event is left to the override file.
+                let found = ctx.external.iter().find_map(|s| {
+                    let entry = s.entries.get(key).filter(|e| e.applies_to(ctx.year))?;
+                    Some((s, entry.lat?, entry.lng?))
+                });
+                if let Some((external, lat, lng)) = found {
+                    info!("Using {} location for {}", external.name, key);
+                    obj.set_lat_lng(lat, lng);
+                    obj.set_provenance(Provenance::resolved(source, attempt.address.clone()));
+                }
+            }
+            LocationSource::Archive => {
+                if let Some(entry) = ctx.archive.get(key) {
+                    self.use_archived(key, obj, entry, attempt);
+                }
+            }
+            LocationSource::Venue => {
+                if !obj.has_venue() {
+                    return false;
+                }
+                let Some(address) = attempt.address.clone() else {
+                    return false;
+                };
+                if let Some(venue) = ctx.venues.get(&normalize::address_key(&address)) {
+                    info!("Reusing known venue for {}: {}", key, venue.address);
+                    obj.set_lat_lng(venue.lat, venue.lng);
+                    obj.set_provenance(Provenance::resolved(source, Some(address)));
+                }
+            }
+            LocationSource::Tba => {
+                if let Some((lat, lng)) = attempt.tba {
+                    obj.set_lat_lng(lat, lng);
+                    obj.set_provenance(Provenance::resolved(source, attempt.address.clone()));
+                }
+            }
+            LocationSource::PlaceId => {
+                let Some(place_id) = obj.place_id().filter(|p| !p.is_empty()) else {
+                    return false;
+                };
+                info!("Place id for {}: {}", key, place_id);
+                if let Some(hit) = self.place_details(key, &place_id).await {
+                    obj.set_lat_lng(hit.lat, hit.lng);
+                    obj.set_geocode_match(GeocodeMatch::PlaceId);
+                    obj.set_provenance(
+                        Provenance::resolved(source, attempt.address.clone())
+                            .with_quality(hit.quality()),
+                    );
+                }
+            }
+            LocationSource::FirstApi => {
+                let Some(code) = obj.first_event_code() else {
+                    return false;
+                };
+                let before = obj.address();
+                if let Some((venue, address)) = obj.venue_address_mut()
+                    && let Err(e) = self
+                        .first_api
+                        .enhance_event_data(ctx.year as i64, &code, venue, address)
+                        .await
+                {
+                    error!("Failed to fetch FIRST data for {}: {}", key, e);
+                }
+                if obj.address() != before {
+                    self.try_geocode(source, key, obj, attempt).await;
+                }
+            }
+            LocationSource::Geocode => {
+                self.try_geocode(source, key, obj, attempt).await;
+            }
+            LocationSource::Gazetteer => {
+                if let Some(hit) = self.gazetteer_lookup(&obj.locality()) {
+                    info!(
+                        "Gazetteer {:?} match for {}: ({}, {})",
+                        hit.precision, key, hit.lat, hit.lng
+                    );
+                    let match_source = match hit.precision {
+                        GazetteerPrecision::PostalCode => GeocodeMatch::PostalCode,
+                        GazetteerPrecision::City => GeocodeMatch::City,
+                    };
+                    obj.set_lat_lng(hit.lat, hit.lng);
+                    obj.set_geocode_match(match_source);
+                    obj.set_provenance(
+                        Provenance::resolved(source, attempt.address.clone()).with_quality(
+                            GeocodeQuality {
+                                match_source,
+                                location_type: None,
+                                partial_match: false,
+                                formatted_address: None,
+                            },
+                        ),
+                    );
+                }
+            }
+        }
+        obj.has_location()
+    }
+
+    /// Reuse an archived location, unless the object's address no longer
+    /// matches the one the location was resolved from, it was only a
+    /// gazetteer centroid, or it came from an override or an external source.
+    /// Entries without an address hash (migrated from old archives) adopt the
+    /// current address.
+    fn use_archived<T: HasLocation>(
+        &self,
+        key: &str,
+        obj: &mut T,
+        entry: &ArchiveEntry,
+        attempt: &Attempt,
+    ) {
+        match entry.provenance.source {
+            LocationSource::Gazetteer => {
+                // A centroid is a stopgap (e.g. the geocode budget ran out), so
+                // give the paid steps another go instead of reusing it.
+                debug!("Not reusing archived gazetteer centroid for {}", key);
+                return;
+            }
+            LocationSource::Override | LocationSource::External => {
+                // Those steps run first, so reaching the archive means the
+                // entry was removed or no longer applies this year.
+                debug!(
+                    "Not reusing archived {} location for {}",
+                    entry.provenance.source.name(),
+                    key
+                );
+                return;
+            }
+            _ => {}
+        }
+        let current = attempt.address.as_deref().map(normalize::address_hash);
+        let mut provenance = entry.provenance.clone();
+        match &provenance.address_hash {
+            Some(hash) if current.as_ref() != Some(hash) => {
+                warn!(
+                    "Address for {} changed since it was archived, re-resolving: {:?} -> {:?}",
+                    key, provenance.address, attempt.address
+                );
+                self.invalidated.lock().unwrap().push(ArchiveInvalidation {
+                    key: key.to_string(),
+                    archived_address: provenance.address,
+                    current_address: attempt.address.clone(),
+                    archived_lat: entry.lat,
+                    archived_lng: entry.lng,
+                });
+                return;
+            }
+            Some(_) => {}
+            None => {
+                provenance.address = attempt.address.clone();
+                provenance.address_hash = current;
+            }
+        }
+        obj.set_lat_lng(entry.lat, entry.lng);
+        obj.set_provenance(provenance);
+    }
+
+    /// Geocode the object's current address, unless an earlier step already tried it.
+    async fn try_geocode<T: HasLocation>(
+        &self,
+        source: LocationSource,
+        key: &str,
+        obj: &mut T,
+        attempt: &mut Attempt,
+    ) {
+        let Some(addr) = obj.address() else {
+            warn!("{} has no address.", key);
+            return;
+        };
+        if !attempt.geocoded.insert(addr.clone()) {
+            return;
+        }
+        warn!("Geocoding {}", key);
+        info!("Address for {}: {}", key, addr);
+        match self.geocode_address(key, &addr).await {
+            Some(hit) => {
+                obj.set_lat_lng(hit.lat, hit.lng);
+                obj.set_geocode_match(GeocodeMatch::Address);
+                obj.set_provenance(
+                    Provenance::resolved(source, attempt.address.clone())
+                        .with_quality(hit.quality()),
+                );
+                info!("Location: ({}, {})", hit.lat, hit.lng);
+            }
+            None => error!("Could not geocode address for {}", key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, sync::Arc};
+
+    use super::*;
+    use crate::{
+        config::Settings, first_api::FirstApiClient, geocoder::external::ExternalSources,
+        map_types::TeamData, tba::types::TbaTeam,
+    };
+
+    const TBA_LOCATION: (f64, f64) = (37.3, -121.9);
+    const ARCHIVED_LOCATION: (f64, f64) = (37.25, -121.96);
+
+    /// A geocoder with no archive, cache, gazetteer or API keys, working in
+    /// its own temp directory.
+    fn geocoder(name: &str) -> (FrcGeocoder, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("frcmap-resolve-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut settings = Settings::default();
+        settings.gazetteer.path = dir.join("gazetteer");
+        let client =
+            Arc::new(reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build());
+        let geocoder = FrcGeocoder::new(
+            Arc::clone(&client),
+            String::new(),
+            dir.clone(),
+            &dir,
+            LocationDict::new(),
+            LocationDict::new(),
+            ExternalSources::default(),
+            FirstApiClient::new(client, ""),
+            &settings,
+        );
+        (geocoder, dir)
+    }
+
+    fn teams() -> HashMap<String, TeamData> {
+        let mut tba: TbaTeam =
+            serde_json::from_value(serde_json::json!({"key": "frc254"})).unwrap();
+        tba.city = Some("San Jose".into());
+        tba.country = Some("USA".into());
+        (tba.lat, tba.lng) = (Some(TBA_LOCATION.0), Some(TBA_LOCATION.1));
+        HashMap::from([("frc254".to_string(), TeamData::new(tba))])
+    }
+
+    fn archive(source: LocationSource, address: Option<&str>) -> Archive {
+        let mut provenance = Provenance::new(source);
+        provenance.address = address.map(str::to_string);
+        provenance.address_hash = address.map(normalize::address_hash);
+        Archive::from([(
+            "frc254".to_string(),
+            ArchiveEntry {
+                lat: ARCHIVED_LOCATION.0,
+                lng: ARCHIVED_LOCATION.1,
+                provenance,
+            },
+        )])
+    }
+
+    /// Resolve the test team through `chain` with `archive`.
+    async fn resolve(name: &str, chain: &[LocationSource], archive: &Archive) -> (TeamData, usize) {
+        let (geocoder, dir) = geocoder(name);
+        let mut teams = teams();
+        let ctx = ResolveContext {
+            year: 2025,
+            chain,
+            overrides: &LocationDict::new(),
+            external: &[],
+            archive,
+            venues: &VenueRegistry::new(),
+        };
+        geocoder.resolve_locations(&mut teams, &ctx).await;
+        let invalidated = geocoder.archive_invalidations().len();
+        std::fs::remove_dir_all(dir).ok();
+        (teams.remove("frc254").unwrap(), invalidated)
+    }
+
+    fn location(team: &TeamData) -> Option<(f64, f64)> {
+        team.lat().zip(team.lng())
+    }
+
+    #[tokio::test]
+    async fn reuses_entry_with_matching_address() {
+        let address = teams()["frc254"].address().unwrap();
+        let archive = archive(LocationSource::Geocode, Some(&address));
+        let chain = [LocationSource::Archive, LocationSource::Tba];
+        let (team, invalidated) = resolve("match", &chain, &archive).await;
+        assert_eq!(location(&team), Some(ARCHIVED_LOCATION));
+        assert_eq!(team.location_source, Some(LocationSource::Archive));
+        assert_eq!(team.resolved_by, Some(LocationSource::Geocode));
+        assert_eq!(invalidated, 0);
+    }
+
+    #[tokio::test]
+    async fn migrated_entry_adopts_current_address() {
+        let archive = archive(LocationSource::Archive, None);
+        let (team, _) = resolve("migrated", &[LocationSource::Archive], &archive).await;
+        assert_eq!(location(&team), Some(ARCHIVED_LOCATION));
+        let provenance = team.provenance().unwrap();
+        assert_eq!(provenance.address, team.address());
+        assert!(provenance.address_hash.is_some());
+    }
+
+    #[tokio::test]
+    async fn address_change_forces_fresh_lookup() {
+        let archive = archive(
+            LocationSource::Geocode,
+            Some("Old School, Fremont, CA, USA"),
+        );
+        let chain = [LocationSource::Archive, LocationSource::Tba];
+        let (team, invalidated) = resolve("mismatch", &chain, &archive).await;
+        assert_eq!(location(&team), Some(TBA_LOCATION));
+        assert_eq!(team.location_source, Some(LocationSource::Tba));
+        assert_eq!(invalidated, 1);
+    }
+
+    #[tokio::test]
+    async fn never_reuses_centroids_or_overrides() {
+        let address = teams()["frc254"].address().unwrap();
+        for source in [
+            LocationSource::Gazetteer,
+            LocationSource::Override,
+            LocationSource::External,
+        ] {
+            let archive = archive(source, Some(&address));
+            let (team, invalidated) =
+                resolve(source.name(), &[LocationSource::Archive], &archive).await;
+            assert_eq!(location(&team), None, "{} entry was reused", source.name());
+            assert_eq!(invalidated, 0);
+        }
+    }
+}
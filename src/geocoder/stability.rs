@@ -0,0 +1,353 @@
+//! Cross-season stability of team locations.
+//!
+//! Consecutive yearly team archives (`all_team_locations_{year}.json`) are
+//! compared, and every team that moved further than a threshold is
+//! classified by what the archives say about it: its address changed (a real
+//! relocation), its location source changed (a new override, or a gazetteer
+//! centroid replaced by a geocode), or neither, which points at geocoder
+//! noise. Entries migrated from version 1 archives carry no address, so their
+//! moves can't be explained either way.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result as AnyhowResult;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    config::{LocationKind, StabilitySettings},
+    fsio, geo,
+    geocoder::{
+        archive::{self, Archive, ArchiveEntry},
+        layout::natural_cmp,
+        resolve::LocationSource,
+    },
+};
+
+/// What a location move is put down to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveKind {
+    /// The address the location was resolved from changed.
+    Relocation,
+    /// A different step of the chain placed the team.
+    SourceChange,
+    /// Same address and source, different coordinate.
+    GeocoderNoise,
+    /// An archive has no address to compare, e.g. a migrated version 1 entry.
+    Unknown,
+}
+
+impl MoveKind {
+    /// Whether the move is expected and shouldn't fail the gate.
+    fn is_explained(self) -> bool {
+        matches!(self, MoveKind::Relocation | MoveKind::SourceChange)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            MoveKind::Relocation => "relocation",
+            MoveKind::SourceChange => "source change",
+            MoveKind::GeocoderNoise => "geocoder noise",
+            MoveKind::Unknown => "unknown",
+        }
+    }
+}
+
+/// One side of a move.
+#[derive(Debug, Clone, Serialize)]
+pub struct MoveEnd {
+    pub season: i64,
+    pub lat: f64,
+    pub lng: f64,
+    pub source: LocationSource,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+impl MoveEnd {
+    fn new(season: i64, entry: &ArchiveEntry) -> Self {
+        Self {
+            season,
+            lat: entry.lat,
+            lng: entry.lng,
+            source: entry.provenance.source,
+            address: entry.provenance.address.clone(),
+        }
+    }
+}
+
+/// A team whose archived location moved between two consecutive seasons.
+#[derive(Debug, Clone, Serialize)]
+pub struct LocationMove {
+    pub key: String,
+    pub distance_km: f64,
+    pub kind: MoveKind,
+    pub from: MoveEnd,
+    pub to: MoveEnd,
+}
+
+fn classify(prev: &ArchiveEntry, cur: &ArchiveEntry) -> MoveKind {
+    let (prev_p, cur_p) = (&prev.provenance, &cur.provenance);
+    match (&prev_p.address_hash, &cur_p.address_hash) {
+        (Some(a), Some(b)) if a != b => MoveKind::Relocation,
+        _ if prev_p.source != cur_p.source => MoveKind::SourceChange,
+        (Some(_), Some(_)) => MoveKind::GeocoderNoise,
+        _ => MoveKind::Unknown,
+    }
+}
+
+/// Teams in both archives that moved more than `max_move_m`, in natural key order.
+pub fn compare(
+    (prev_season, prev): (i64, &Archive),
+    (season, cur): (i64, &Archive),
+    max_move_m: f64,
+) -> Vec<LocationMove> {
+    let mut moves: Vec<LocationMove> = cur
+        .iter()
+        .filter_map(|(key, entry)| {
+            let old = prev.get(key)?;
+            let distance_m = geo::distance_m(old.lat, old.lng, entry.lat, entry.lng);
+            (distance_m > max_move_m).then(|| LocationMove {
+                key: key.clone(),
+                distance_km: (distance_m / 100.0).round() / 10.0,
+                kind: classify(old, entry),
+                from: MoveEnd::new(prev_season, old),
+                to: MoveEnd::new(season, entry),
+            })
+        })
+        .collect();
+    moves.sort_by(|a, b| natural_cmp(&a.key, &b.key));
+    moves
+}
+
+fn print_move(m: &LocationMove) {
+    println!(
+        "{} {}->{}: moved {} km ({}): ({}, {}) via {} -> ({}, {}) via {}",
+        m.key,
+        m.from.season,
+        m.to.season,
+        m.distance_km,
+        m.kind.label(),
+        m.from.lat,
+        m.from.lng,
+        m.from.source.name(),
+        m.to.lat,
+        m.to.lng,
+        m.to.source.name()
+    );
+    if m.kind == MoveKind::Relocation {
+        println!(
+            "    {} -> {}",
+            m.from.address.as_deref().unwrap_or("?"),
+            m.to.address.as_deref().unwrap_or("?")
+        );
+    }
+}
+
+/// Print a count of `moves` by kind.
+fn print_summary(moves: &[LocationMove]) {
+    let kinds = [
+        MoveKind::Relocation,
+        MoveKind::SourceChange,
+        MoveKind::GeocoderNoise,
+        MoveKind::Unknown,
+    ];
+    let counts: Vec<String> = kinds
+        .iter()
+        .map(|&kind| {
+            let n = moves.iter().filter(|m| m.kind == kind).count();
+            format!("{} {}", n, kind.label())
+        })
+        .collect();
+    println!("{} moves: {}", moves.len(), counts.join(", "));
+}
+
+/// Handle the `stability` subcommand: report moves between every pair of
+/// consecutive team archives, or only the pair ending in `season`.
+pub fn run_command(
+    archive_path: &Path,
+    season: Option<u32>,
+    max_move_km: f64,
+    report: Option<&Path>,
+) -> AnyhowResult<()> {
+    let years: Vec<(i64, PathBuf)> =
+        archive::archive_years(archive_path, archive::archive_prefix(LocationKind::Teams))
+            .into_iter()
+            .collect();
+    anyhow::ensure!(
+        years.len() >= 2,
+        "Need at least two yearly team archives in {}",
+        archive_path.display()
+    );
+
+    let mut moves = Vec::new();
+    let mut compared = 0;
+    for pair in years.windows(2) {
+        let ((prev_year, prev_path), (year, path)) = (&pair[0], &pair[1]);
+        if season.is_some_and(|s| i64::from(s) != *year) {
+            continue;
+        }
+        compared += 1;
+        let prev = archive::load_archive(prev_path)?;
+        let cur = archive::load_archive(path)?;
+        let found = compare((*prev_year, &prev), (*year, &cur), max_move_km * 1000.0);
+        found.iter().for_each(print_move);
+        moves.extend(found);
+    }
+    if let Some(season) = season {
+        anyhow::ensure!(
+            compared > 0,
+            "No team archives for {} and a season before it",
+            season
+        );
+    }
+    print_summary(&moves);
+
+    if let Some(path) = report {
+        fsio::write_atomic(path, serde_json::to_string_pretty(&moves)? + "\n")?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Gate for `generate`: fail when teams in `cur`, the locations resolved for
+/// `year` and not yet archived, moved since the previous season's archive
+/// without an address or source change to explain it.
+pub fn check_gate(
+    archive_path: &Path,
+    year: u32,
+    cur: &Archive,
+    settings: &StabilitySettings,
+) -> AnyhowResult<()> {
+    let years = archive::archive_years(archive_path, archive::archive_prefix(LocationKind::Teams));
+    let Some((prev_year, prev_path)) = years.range(..i64::from(year)).next_back() else {
+        info!(
+            "No team archive before {}, skipping the stability check",
+            year
+        );
+        return Ok(());
+    };
+
+    let prev = archive::load_archive(prev_path)?;
+    let moves = compare(
+        (*prev_year, &prev),
+        (i64::from(year), cur),
+        settings.max_move_km * 1000.0,
+    );
+    let unexplained: Vec<&LocationMove> = moves.iter().filter(|m| !m.kind.is_explained()).collect();
+    for m in &moves {
+        if m.kind.is_explained() {
+            info!(
+                "{} moved {} km since {} ({})",
+                m.key,
+                m.distance_km,
+                prev_year,
+                m.kind.label()
+            );
+        } else {
+            warn!(
+                "{} moved {} km since {} ({}): ({}, {}) -> ({}, {})",
+                m.key,
+                m.distance_km,
+                prev_year,
+                m.kind.label(),
+                m.from.lat,
+                m.from.lng,
+                m.to.lat,
+                m.to.lng
+            );
+        }
+    }
+    anyhow::ensure!(
+        unexplained.is_empty(),
+        "Stability check failed: {} team(s) moved more than {} km since {} without an address or source change",
+        unexplained.len(),
+        settings.max_move_km,
+        prev_year
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geocoder::types::Provenance;
+
+    fn entry(lat: f64, source: LocationSource, address: Option<&str>) -> ArchiveEntry {
+        let mut provenance = Provenance::new(source);
+        provenance.address = address.map(str::to_string);
+        provenance.address_hash = address.map(|a| format!("hash of {}", a));
+        ArchiveEntry {
+            lat,
+            lng: -122.0,
+            provenance,
+        }
+    }
+
+    fn kind(prev: ArchiveEntry, cur: ArchiveEntry) -> MoveKind {
+        classify(&prev, &cur)
+    }
+
+    #[test]
+    fn classifies_each_kind_of_move() {
+        use LocationSource::{Gazetteer, Geocode, Override};
+        let (a, b) = (Some("1 Old St"), Some("2 New St"));
+
+        // A changed address wins even when the source changed too.
+        assert_eq!(
+            kind(entry(37.0, Geocode, a), entry(37.1, Geocode, b)),
+            MoveKind::Relocation
+        );
+        assert_eq!(
+            kind(entry(37.0, Gazetteer, a), entry(37.1, Geocode, b)),
+            MoveKind::Relocation
+        );
+        // Same address, or one side without one: the source decides.
+        assert_eq!(
+            kind(entry(37.0, Gazetteer, a), entry(37.1, Geocode, a)),
+            MoveKind::SourceChange
+        );
+        assert_eq!(
+            kind(entry(37.0, Geocode, None), entry(37.1, Override, a)),
+            MoveKind::SourceChange
+        );
+        assert_eq!(
+            kind(entry(37.0, Geocode, a), entry(37.1, Geocode, a)),
+            MoveKind::GeocoderNoise
+        );
+        assert_eq!(
+            kind(entry(37.0, Geocode, None), entry(37.1, Geocode, a)),
+            MoveKind::Unknown
+        );
+        assert_eq!(
+            kind(entry(37.0, Geocode, a), entry(37.1, Geocode, None)),
+            MoveKind::Unknown
+        );
+    }
+
+    #[test]
+    fn compares_only_moves_past_the_threshold() {
+        use LocationSource::Geocode;
+        let address = Some("1 Main St");
+        // 0.01 degrees of latitude is about 1.1 km.
+        let prev = Archive::from([
+            ("frc10".to_string(), entry(37.0, Geocode, address)),
+            ("frc9".to_string(), entry(37.0, Geocode, address)),
+            ("frc8".to_string(), entry(37.0, Geocode, address)),
+            ("frc7".to_string(), entry(37.0, Geocode, address)),
+        ]);
+        let cur = Archive::from([
+            ("frc10".to_string(), entry(37.02, Geocode, address)),
+            ("frc9".to_string(), entry(37.005, Geocode, address)),
+            ("frc8".to_string(), entry(37.1, Geocode, address)),
+            ("frc6".to_string(), entry(40.0, Geocode, address)),
+        ]);
+        let moves = compare((2024, &prev), (2025, &cur), 1000.0);
+        let keys: Vec<&str> = moves.iter().map(|m| m.key.as_str()).collect();
+        assert_eq!(keys, ["frc8", "frc10"]);
+        assert_eq!(moves[1].distance_km, 2.2);
+        assert_eq!((moves[1].from.season, moves[1].to.season), (2024, 2025));
+        assert_eq!(moves[1].kind, MoveKind::GeocoderNoise);
+    }
+}
@@ -1,10 +1,14 @@
 use std::collections::HashMap;
 
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::geocoder::{archive::TOOL_VERSION, normalize, resolve::LocationSource};
+
 /// A location override entry — e.g. from `locations/teams.json`.
-/// May contain `lat`/`lng`, or just `"ignore": true`.
+/// May contain `lat`/`lng`, or just `"ignore": true`, optionally limited to
+/// a range of seasons.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LocationOverride {
     #[serde(default)]
@@ -13,14 +17,141 @@ pub struct LocationOverride {
     pub lng: Option<f64>,
     #[serde(default)]
     pub ignore: Option<bool>,
+    /// First season the override applies to.
+    #[serde(default)]
+    pub valid_from: Option<u32>,
+    /// Last season the override applies to, e.g. before the team moved.
+    #[serde(default)]
+    pub valid_until: Option<u32>,
+    /// Why the override is needed.
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Where the coordinates came from (an issue, a team member, a map link).
+    #[serde(default)]
+    pub source: Option<String>,
+    /// When the override was added.
+    #[serde(default)]
+    pub added: Option<NaiveDate>,
 
     #[serde(flatten)]
     pub extra: HashMap<String, Value>,
 }
 
+impl LocationOverride {
+    /// Whether the override applies in season `year`.
+    pub fn applies_to(&self, year: u32) -> bool {
+        self.valid_from.is_none_or(|from| year >= from)
+            && self.valid_until.is_none_or(|until| year <= until)
+    }
+}
+
 /// Type alias matching the Python `LocationDict = Dict[str, Dict[str, float]]`.
 pub type LocationDict = HashMap<String, LocationOverride>;
 
+/// How a geocoded location was matched.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeocodeMatch {
+    /// Resolved from TBA's `gmaps_place_id` through Place Details.
+    PlaceId,
+    /// Resolved by free-text geocoding of the address.
+    #[default]
+    Address,
+    /// Postal-code centroid from the offline gazetteer.
+    PostalCode,
+    /// City centroid from the offline gazetteer.
+    City,
+}
+
+/// Provider metadata describing how good a geocoded location is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeocodeQuality {
+    pub match_source: GeocodeMatch,
+    /// Provider-specific precision, e.g. Google's `ROOFTOP` or `APPROXIMATE`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_type: Option<String>,
+    #[serde(default)]
+    pub partial_match: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub formatted_address: Option<String>,
+}
+
+impl GeocodeQuality {
+    /// Rough accuracy radius in metres implied by the match, for the map to
+    /// show how far a marker may be off.
+    pub fn accuracy_m(&self) -> Option<u32> {
+        match self.match_source {
+            GeocodeMatch::PostalCode => return Some(5_000),
+            GeocodeMatch::City => return Some(15_000),
+            GeocodeMatch::PlaceId | GeocodeMatch::Address => {}
+        }
+        let radius = match self.location_type.as_deref()? {
+            "ROOFTOP" => 25,
+            "RANGE_INTERPOLATED" => 100,
+            "GEOMETRIC_CENTER" => 500,
+            "APPROXIMATE" => 5_000,
+            _ => return None,
+        };
+        // A partial match may have dropped part of the address.
+        Some(if self.partial_match {
+            radius.max(5_000)
+        } else {
+            radius
+        })
+    }
+}
+
+/// Where a location came from, as recorded in the location archive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Provenance {
+    pub source: LocationSource,
+    /// The object's address when the location was resolved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    /// `normalize::address_hash` of `address`; a mismatch with the current
+    /// address invalidates the archived location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address_hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_on: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quality: Option<GeocodeQuality>,
+    /// Version of the tool that resolved the location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_version: Option<String>,
+}
+
+impl Provenance {
+    /// Provenance with only a source, e.g. for entries migrated from an old archive.
+    pub fn new(source: LocationSource) -> Self {
+        Self {
+            source,
+            address: None,
+            address_hash: None,
+            resolved_on: None,
+            quality: None,
+            tool_version: None,
+        }
+    }
+
+    /// Provenance for a location resolved today by this build.
+    pub fn resolved(source: LocationSource, address: Option<String>) -> Self {
+        Self {
+            source,
+            address_hash: address.as_deref().map(normalize::address_hash),
+            address,
+            resolved_on: Some(Utc::now().date_naive()),
+            quality: None,
+            tool_version: Some(TOOL_VERSION.to_string()),
+        }
+    }
+
+    pub fn with_quality(mut self, quality: GeocodeQuality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+}
+
 // ── Google Maps Geocoding API response types ───────────────────
 
 #[derive(Debug, Deserialize)]
@@ -32,19 +163,37 @@ pub struct GeocodeResponse {
     pub status: String,
 }
 
+/// Place Details response; `result` has the same shape as a geocode result.
+#[derive(Debug, Deserialize)]
+pub struct PlaceDetailsResponse {
+    #[serde(default)]
+    pub result: Option<GeocodeResult>,
+    #[serde(default)]
+    pub status: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct GeocodeResult {
     #[serde(default)]
     pub geometry: Option<GeocodeGeometry>,
+    #[serde(default)]
+    pub formatted_address: Option<String>,
+    #[serde(default)]
+    pub place_id: Option<String>,
+    #[serde(default)]
+    pub partial_match: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct GeocodeGeometry {
     #[serde(default)]
     pub location: Option<GeocodeLocation>,
+    /// `ROOFTOP`, `RANGE_INTERPOLATED`, `GEOMETRIC_CENTER` or `APPROXIMATE`.
+    #[serde(default)]
+    pub location_type: Option<String>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeocodeLocation {
     pub lat: f64,
     pub lng: f64,
@@ -0,0 +1,739 @@
+//! The `validate` subcommand: lints the override files and archives, loads
+//! the settings file, and checks that every other JSON, TOML and YAML file in
+//! the repository parses.
+//!
+//! serde_json silently keeps the last of two duplicate keys and
+//! `LocationOverride` accepts any extra field, so a typo'd `"lon"` or a
+//! pasted-twice team quietly does nothing. This catches those before the
+//! pipeline runs.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result as AnyhowResult;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{Map, Value};
+
+use crate::{
+    config::{LocationKind, Settings},
+    geocoder::{
+        archive::{self, ArchiveEntry, VenueRegistry},
+        coords,
+        layout::natural_cmp,
+        override_formats::{self, Format},
+        overrides::OVERRIDE_FIELDS,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+struct Finding {
+    severity: Severity,
+    path: PathBuf,
+    /// Key or JSON path within the file, empty for file-level problems.
+    location: String,
+    message: String,
+}
+
+#[derive(Default)]
+struct Report {
+    findings: Vec<Finding>,
+    files: Vec<PathBuf>,
+}
+
+impl Report {
+    fn push(&mut self, severity: Severity, path: &Path, location: &str, message: String) {
+        self.findings.push(Finding {
+            severity,
+            path: path.to_path_buf(),
+            location: location.to_string(),
+            message,
+        });
+    }
+
+    fn error(&mut self, path: &Path, location: &str, message: String) {
+        self.push(Severity::Error, path, location, message);
+    }
+
+    fn warning(&mut self, path: &Path, location: &str, message: String) {
+        self.push(Severity::Warning, path, location, message);
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.findings
+            .iter()
+            .filter(|f| f.severity == severity)
+            .count()
+    }
+
+    /// Read and parse a JSON file, reporting syntax errors and duplicate keys.
+    fn load(&mut self, path: &Path) -> Option<Value> {
+        self.files.push(path.to_path_buf());
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.error(path, "", format!("failed to read: {}", e));
+                return None;
+            }
+        };
+        let mut duplicates = Vec::new();
+        let mut deserializer = serde_json::Deserializer::from_str(&content);
+        let parsed = CheckedValue {
+            path: String::new(),
+            duplicates: &mut duplicates,
+        }
+        .deserialize(&mut deserializer)
+        .and_then(|value| deserializer.end().map(|_| value));
+        match parsed {
+            Ok(value) => {
+                for key in duplicates {
+                    self.error(
+                        path,
+                        &key,
+                        "duplicate key; only the last one is used".into(),
+                    );
+                }
+                Some(value)
+            }
+            Err(e) => {
+                self.error(path, "", format!("invalid JSON: {}", e));
+                None
+            }
+        }
+    }
+}
+
+impl Report {
+    /// Check that a TOML or YAML file parses; JSON goes through `load`.
+    fn load_other(&mut self, path: &Path) {
+        let ext = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or_default();
+        if ext == "json" {
+            self.load(path);
+            return;
+        }
+        self.files.push(path.to_path_buf());
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) => {
+                self.error(path, "", format!("failed to read: {}", e));
+                return;
+            }
+        };
+        let problem = match ext {
+            "toml" => toml::from_str::<toml::Table>(&content)
+                .err()
+                .map(|e| format!("invalid TOML: {}", e)),
+            _ => serde_yaml_ng::from_str::<serde_yaml_ng::Value>(&content)
+                .err()
+                .map(|e| format!("invalid YAML: {}", e)),
+        };
+        if let Some(problem) = problem {
+            self.error(path, "", problem);
+        }
+    }
+}
+
+/// Deserializes JSON into a `Value`, recording the path of every key that
+/// repeats within its object.
+struct CheckedValue<'a> {
+    path: String,
+    duplicates: &'a mut Vec<String>,
+}
+
+impl CheckedValue<'_> {
+    fn child(&mut self, name: &str) -> CheckedValue<'_> {
+        CheckedValue {
+            path: if self.path.is_empty() {
+                name.to_string()
+            } else {
+                format!("{}.{}", self.path, name)
+            },
+            duplicates: self.duplicates,
+        }
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for CheckedValue<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CheckedValue<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(v.into())
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(self.child(&format!("[{}]", items.len())))? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Value, A::Error> {
+        let mut object = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(self.child(&key))?;
+            if object.insert(key.clone(), value).is_some() {
+                let path = self.child(&key).path;
+                self.duplicates.push(path);
+            }
+        }
+        Ok(Value::Object(object))
+    }
+}
+
+/// Top-level keys of a JSON object in file order, duplicates included.
+fn key_order(path: &Path) -> Vec<String> {
+    struct Keys(Vec<String>);
+    impl<'de> de::Deserialize<'de> for Keys {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct KeyVisitor;
+            impl<'de> Visitor<'de> for KeyVisitor {
+                type Value = Keys;
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a JSON object")
+                }
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Keys, A::Error> {
+                    let mut keys = Vec::new();
+                    while let Some(key) = map.next_key::<String>()? {
+                        map.next_value::<de::IgnoredAny>()?;
+                        keys.push(key);
+                    }
+                    Ok(Keys(keys))
+                }
+            }
+            deserializer.deserialize_map(KeyVisitor)
+        }
+    }
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<Keys>(&content).ok())
+        .map(|keys| keys.0)
+        .unwrap_or_default()
+}
+
+/// Why `key` isn't a valid team (`frc254`) or event (`2024casj`) key, if it isn't.
+pub(crate) fn key_problem(key: &str, kind: LocationKind) -> Option<String> {
+    let (valid, example) = match kind {
+        LocationKind::Teams => (
+            key.strip_prefix("frc").is_some_and(|number| {
+                !number.starts_with('0')
+                    && !number.is_empty()
+                    && number.chars().all(|c| c.is_ascii_digit())
+            }),
+            "frc254",
+        ),
+        LocationKind::Events => (
+            key.len() > 4
+                && (key.starts_with("19") || key.starts_with("20"))
+                && key.as_bytes()[..4].iter().all(u8::is_ascii_digit)
+                && key[4..]
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()),
+            "2024casj",
+        ),
+    };
+    if valid {
+        return None;
+    }
+    let lower = key.trim().to_lowercase();
+    if lower != key && key_problem(&lower, kind).is_none() {
+        return Some(format!("key should be written '{}'", lower));
+    }
+    let noun = match kind {
+        LocationKind::Teams => "team",
+        LocationKind::Events => "event",
+    };
+    Some(format!("not a valid {} key (like '{}')", noun, example))
+}
+
+/// Levenshtein distance, for spotting typo'd field names.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = (prev + usize::from(ca != cb)).min(row[j] + 1).min(cur + 1);
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// The known field an unknown field name was probably meant to be.
+fn suggest_field(field: &str) -> Option<&'static str> {
+    let lower = field.to_lowercase();
+    if archive::LAT_NAMES.contains(&lower.as_str()) {
+        return Some("lat");
+    }
+    if archive::LNG_NAMES.contains(&lower.as_str()) {
+        return Some("lng");
+    }
+    OVERRIDE_FIELDS
+        .iter()
+        .copied()
+        .filter(|known| edit_distance(&lower, known) <= 2)
+        .min_by_key(|known| edit_distance(&lower, known))
+}
+
+/// Check a coordinate pair, where either half may be missing or not a
+/// number. `null` counts as missing, as it does when the files are loaded.
+fn check_coordinates(report: &mut Report, path: &Path, key: &str, entry: &Map<String, Value>) {
+    let mut number = |field: &str| match entry.get(field) {
+        None | Some(Value::Null) => None,
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(other) => {
+            report.error(
+                path,
+                key,
+                format!("'{}' must be a number, not {}", field, other),
+            );
+            Some(f64::NAN)
+        }
+    };
+    match (number("lat"), number("lng")) {
+        (Some(lat), Some(lng)) if !lat.is_nan() && !lng.is_nan() => {
+            if let Err(e) = coords::validate(lat, lng) {
+                report.error(path, key, e.to_string());
+            }
+        }
+        (Some(_), None) => report.error(path, key, "has 'lat' but no 'lng'".into()),
+        (None, Some(_)) => report.error(path, key, "has 'lng' but no 'lat'".into()),
+        _ => {}
+    }
+}
+
+/// Check the season range and the descriptive fields of an override.
+fn check_override_metadata(
+    report: &mut Report,
+    path: &Path,
+    key: &str,
+    entry: &Map<String, Value>,
+) {
+    let mut season = |field: &str| match entry.get(field) {
+        None | Some(Value::Null) => None,
+        Some(value) => match value.as_u64().filter(|y| (1992..=2100).contains(y)) {
+            Some(year) => Some(year),
+            None => {
+                report.error(
+                    path,
+                    key,
+                    format!("'{}' must be a season year, not {}", field, value),
+                );
+                None
+            }
+        },
+    };
+    if let (Some(from), Some(until)) = (season("valid_from"), season("valid_until"))
+        && from > until
+    {
+        report.error(
+            path,
+            key,
+            format!("valid_from {} is after valid_until {}", from, until),
+        );
+    }
+    for field in ["reason", "source"] {
+        if let Some(value) = entry.get(field).filter(|v| !v.is_string() && !v.is_null()) {
+            report.error(
+                path,
+                key,
+                format!("'{}' must be a string, not {}", field, value),
+            );
+        }
+    }
+    match entry.get("added") {
+        None | Some(Value::Null) => {}
+        Some(Value::String(date)) if date.parse::<chrono::NaiveDate>().is_ok() => {}
+        Some(other) => report.error(
+            path,
+            key,
+            format!("'added' must be a YYYY-MM-DD date, not {}", other),
+        ),
+    }
+}
+
+/// Lint a manual override file (`locations/teams.json`, `locations/events.json`).
+fn check_override_file(report: &mut Report, path: &Path, kind: LocationKind) {
+    // TOML and YAML parsers reject duplicate keys themselves.
+    let (entries, order) = match Format::of(path) {
+        Format::Json => {
+            let Some(value) = report.load(path) else {
+                return;
+            };
+            let Value::Object(entries) = value else {
+                report.error(
+                    path,
+                    "",
+                    "must be a JSON object keyed by team or event".into(),
+                );
+                return;
+            };
+            (entries, key_order(path))
+        }
+        _ => {
+            report.files.push(path.to_path_buf());
+            match override_formats::read_entries(path) {
+                Ok(entries) => {
+                    let order = entries.iter().map(|(k, _)| k.clone()).collect();
+                    (entries.into_iter().collect(), order)
+                }
+                Err(e) => {
+                    report.error(path, "", format!("{:#}", e));
+                    return;
+                }
+            }
+        }
+    };
+
+    for (key, entry) in &entries {
+        if key == "_comment" {
+            continue;
+        }
+        if let Some(problem) = key_problem(key, kind) {
+            report.error(path, key, problem);
+        }
+        let Value::Object(entry) = entry else {
+            report.error(path, key, format!("entry must be an object, not {}", entry));
+            continue;
+        };
+        check_coordinates(report, path, key, entry);
+        match entry.get("ignore") {
+            None | Some(Value::Bool(_)) => {}
+            Some(other) => report.error(
+                path,
+                key,
+                format!("'ignore' must be true or false, not {}", other),
+            ),
+        }
+        check_override_metadata(report, path, key, entry);
+        for field in entry.keys() {
+            if OVERRIDE_FIELDS.contains(&field.as_str()) {
+                continue;
+            }
+            match suggest_field(field) {
+                Some(known) => report.error(
+                    path,
+                    key,
+                    format!("unknown field '{}' (did you mean '{}'?)", field, known),
+                ),
+                None => report.warning(path, key, format!("unknown field '{}' is ignored", field)),
+            }
+        }
+        let located = ["lat", "lng"]
+            .iter()
+            .any(|field| entry.get(*field).is_some_and(|v| !v.is_null()));
+        let has_effect = located || entry.get("ignore") == Some(&Value::Bool(true));
+        if !has_effect {
+            report.warning(path, key, "entry has no effect".into());
+        }
+    }
+
+    // Ordering: `_comment` first, then keys in natural order.
+    if order.iter().skip(1).any(|k| k == "_comment") {
+        report.warning(path, "_comment", "should be the first key".into());
+    }
+    let keys: Vec<&String> = order.iter().filter(|k| *k != "_comment").collect();
+    for pair in keys.windows(2) {
+        if natural_cmp(pair[0], pair[1]) == Ordering::Greater {
+            let before = keys
+                .iter()
+                .find(|k| natural_cmp(k, pair[1]) == Ordering::Greater)
+                .unwrap_or(&pair[0]);
+            report.warning(
+                path,
+                pair[1],
+                format!("out of order; should come before '{}'", before),
+            );
+        }
+    }
+}
+
+/// Lint a location archive written by the pipeline.
+fn check_archive_file(report: &mut Report, path: &Path, kind: LocationKind) {
+    let Some(value) = report.load(path) else {
+        return;
+    };
+    let entries = match value.get("version") {
+        None => value,
+        Some(version) if version.as_u64() == Some(u64::from(archive::ARCHIVE_VERSION)) => {
+            value.get("entries").cloned().unwrap_or(Value::Null)
+        }
+        Some(version) => {
+            report.error(
+                path,
+                "version",
+                format!("unsupported archive version {}", version),
+            );
+            return;
+        }
+    };
+    let Value::Object(entries) = entries else {
+        report.error(path, "", "archive entries must be a JSON object".into());
+        return;
+    };
+    for (key, entry) in &entries {
+        if let Some(problem) = key_problem(key, kind) {
+            report.error(path, key, problem);
+        }
+        let Value::Object(fields) = entry else {
+            report.error(path, key, format!("entry must be an object, not {}", entry));
+            continue;
+        };
+        check_coordinates(report, path, key, fields);
+        if fields.contains_key("source")
+            && let Err(e) = serde_json::from_value::<ArchiveEntry>(entry.clone())
+        {
+            report.error(path, key, format!("invalid archive entry: {}", e));
+        }
+    }
+}
+
+fn check_venue_registry(report: &mut Report, path: &Path) {
+    let Some(value) = report.load(path) else {
+        return;
+    };
+    match serde_json::from_value::<VenueRegistry>(value) {
+        Ok(venues) => {
+            for (key, venue) in venues {
+                if let Err(e) = coords::validate(venue.lat, venue.lng) {
+                    report.error(path, &key, e.to_string());
+                }
+            }
+        }
+        Err(e) => report.error(path, "", format!("invalid venue registry: {}", e)),
+    }
+}
+
+/// JSON, TOML and YAML files under `dir`, skipping hidden directories and `target`.
+fn data_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                data_files(&path, files);
+            }
+        } else if path
+            .extension()
+            .is_some_and(|ext| ["json", "toml", "yaml", "yml"].iter().any(|e| ext == *e))
+        {
+            files.push(path);
+        }
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Handle the `validate` subcommand. Fails if anything is wrong, or with
+/// `strict`, if there are warnings too.
+pub fn run_command(
+    teams_path: &Path,
+    events_path: &Path,
+    archive_path: &Path,
+    settings_path: &Path,
+    root: &Path,
+    strict: bool,
+) -> AnyhowResult<()> {
+    let mut report = Report::default();
+
+    if settings_path.is_file() {
+        report.files.push(settings_path.to_path_buf());
+        if let Err(e) = Settings::load(&settings_path.to_path_buf()) {
+            report.error(settings_path, "", format!("{:#}", e));
+        }
+    }
+
+    for (path, kind) in [
+        (teams_path, LocationKind::Teams),
+        (events_path, LocationKind::Events),
+    ] {
+        if path.is_file() {
+            check_override_file(&mut report, path, kind);
+        }
+    }
+
+    let mut archives = Vec::new();
+    data_files(archive_path, &mut archives);
+    for path in archives {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with("all_team_locations") {
+            check_archive_file(&mut report, &path, LocationKind::Teams);
+        } else if name.starts_with("all_event_locations") {
+            check_archive_file(&mut report, &path, LocationKind::Events);
+        } else if name == archive::VENUE_REGISTRY {
+            check_venue_registry(&mut report, &path);
+        }
+    }
+
+    // Everything else only has to parse.
+    let mut others = Vec::new();
+    data_files(root, &mut others);
+    for path in others {
+        if !report.files.iter().any(|seen| same_file(seen, &path)) {
+            report.load_other(&path);
+        }
+    }
+
+    for finding in &report.findings {
+        let location = if finding.location.is_empty() {
+            String::new()
+        } else {
+            format!(" {}:", finding.location)
+        };
+        println!(
+            "{}: {}:{} {}",
+            finding.severity,
+            finding.path.display(),
+            location,
+            finding.message
+        );
+    }
+    let (errors, warnings) = (
+        report.count(Severity::Error),
+        report.count(Severity::Warning),
+    );
+    println!(
+        "Checked {} files: {} errors, {} warnings",
+        report.files.len(),
+        errors,
+        warnings
+    );
+    anyhow::ensure!(
+        errors == 0 && !(strict && warnings > 0),
+        "Validation failed"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `content` to a scratch file and lint it with `check`.
+    fn lint(name: &str, content: &str, check: impl FnOnce(&mut Report, &Path)) -> Vec<String> {
+        let dir =
+            std::env::temp_dir().join(format!("frcmap-validate-{}-{}", std::process::id(), name));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.json", name));
+        std::fs::write(&path, content).unwrap();
+        let mut report = Report::default();
+        check(&mut report, &path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        report
+            .findings
+            .iter()
+            .filter(|f| f.message.starts_with("duplicate key"))
+            .map(|f| f.location.clone())
+            .collect()
+    }
+
+    #[test]
+    fn flags_duplicate_override_keys() {
+        let duplicates = lint(
+            "override",
+            r#"{
+                "frc1": {"lat": 1.0, "lng": 2.0},
+                "frc2": {"lat": 1.0, "lat": 3.0, "lng": 2.0},
+                "frc1": {"ignore": true}
+            }"#,
+            |report, path| check_override_file(report, path, LocationKind::Teams),
+        );
+        assert_eq!(duplicates, ["frc2.lat", "frc1"]);
+    }
+
+    #[test]
+    fn flags_duplicate_archive_keys() {
+        let duplicates = lint(
+            "archive",
+            r#"{
+                "version": 2,
+                "entries": {
+                    "frc1": {"lat": 1.0, "lng": 2.0},
+                    "frc1": {"lat": 1.0, "lng": 2.0}
+                }
+            }"#,
+            |report, path| check_archive_file(report, path, LocationKind::Teams),
+        );
+        assert_eq!(duplicates, ["entries.frc1"]);
+    }
+
+    #[test]
+    fn flags_duplicate_venue_keys() {
+        let duplicates = lint(
+            "venues",
+            r#"{
+                "venue": {"lat": 1.0, "lng": 2.0, "address": "A", "events": ["2024casj"]},
+                "venue": {"lat": 1.0, "lng": 2.0, "address": "B", "events": ["2024cada"]}
+            }"#,
+            check_venue_registry,
+        );
+        assert_eq!(duplicates, ["venue"]);
+    }
+}
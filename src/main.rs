@@ -1,6 +1,8 @@
 mod config;
 mod first_api;
 mod frcmap;
+mod fsio;
+mod geo;
 mod geocoder;
 mod http_client;
 mod map_types;
@@ -13,7 +15,7 @@ use clap::Parser;
 use env_logger::{Builder, Env};
 use log::info;
 
-use config::{ApiKeys, Cli};
+use config::{ApiKeys, Cli, Command, Settings};
 use frcmap::FrcMap;
 
 #[tokio::main]
@@ -23,16 +25,81 @@ async fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+
+    match &cli.command {
+        None | Some(Command::Generate) => generate(cli).await,
+        Some(Command::GeocodeCache { action }) => geocoder::cache::run_command(&cli.cache, action),
+        Some(Command::Overrides { action }) => {
+            let settings = Settings::load(&cli.settings)?;
+            geocoder::overrides::run_command(
+                &cli.teams,
+                &cli.events,
+                &settings.gazetteer.path,
+                &cli.output,
+                cli.year,
+                action,
+            )
+        }
+        Some(Command::Archive { action }) => {
+            geocoder::archive::run_command(&cli.archive, &cli.output, cli.year, action)
+        }
+        Some(Command::Outliers {
+            max_distance_km,
+            report,
+        }) => {
+            let settings = Settings::load(&cli.settings)?;
+            geocoder::outliers::run_command(
+                &cli.output,
+                &settings.gazetteer.path,
+                cli.year,
+                *max_distance_km,
+                report.as_deref(),
+            )
+        }
+        Some(Command::Stability {
+            season,
+            max_move_km,
+            report,
+        }) => {
+            let settings = Settings::load(&cli.settings)?;
+            geocoder::stability::run_command(
+                &cli.archive,
+                *season,
+                max_move_km.unwrap_or(settings.stability.max_move_km),
+                report.as_deref(),
+            )
+        }
+        Some(Command::Validate { root, strict }) => geocoder::validate::run_command(
+            &cli.teams,
+            &cli.events,
+            &cli.archive,
+            &cli.settings,
+            root,
+            *strict,
+        ),
+    }
+}
+
+async fn generate(cli: Cli) -> Result<()> {
     info!("Starting FRC Season Map Generator (year = {})", cli.year);
 
     // Load API keys
     let keys = ApiKeys::load(&cli.api_keys)?;
 
+    // Load pipeline settings
+    let mut settings = Settings::load(&cli.settings)?;
+    if cli.retry_failed {
+        settings.geocoding.retry_failed_after_days = 0;
+    }
+
     // Ensure directories exist
     ensure_dir(&cli.cache, "cache")?;
     ensure_dir(&cli.archive, "archive")?;
     ensure_dir(&cli.output, "output")?;
 
+    // Hold the archive for the whole run so concurrent runs can't clobber it
+    let _archive_lock = fsio::DirLock::acquire(&cli.archive)?;
+
     // Load manual location overrides
     let team_overrides = if cli.teams.exists() {
         info!("Loading team locations from: {}", cli.teams.display());
@@ -51,6 +118,12 @@ async fn main() -> Result<()> {
     // Build shared HTTP client with persistent cache
     let client = http_client::build_cached_client(&cli.cache)?;
 
+    // Fetch offline gazetteer files that aren't present yet
+    geocoder::gazetteer::ensure_files(&client, &settings.gazetteer).await?;
+
+    // Load third-party location overrides
+    let external = geocoder::external::load_sources(&client, &settings.external, &cli.cache).await;
+
     // Create main object
     let mut map = FrcMap::new(
         client,
@@ -59,9 +132,12 @@ async fn main() -> Result<()> {
         &keys.first_token,
         cli.year,
         cli.archive,
+        cli.cache,
         team_overrides,
         event_overrides,
+        external,
         cli.debug_path,
+        &settings,
     );
 
     map.generate().await?;
@@ -1,6 +1,37 @@
 use serde::{Deserialize, Serialize};
 
-use crate::tba::types::{TbaEvent, TbaTeam};
+use crate::{
+    geocoder::{
+        gazetteer::Locality,
+        make_event_address, make_team_address,
+        resolve::LocationSource,
+        types::{GeocodeMatch, Provenance},
+    },
+    tba::types::{TbaEvent, TbaTeam},
+};
+
+/// Display-only shift, in degrees, applied on top of the canonical location
+/// so co-located markers don't overlap. Never written to archives.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DisplayOffset {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+/// City, state and country derived from a team's location, for the fields
+/// TBA leaves blank. Only those fields are set; the TBA fields are left as
+/// they are.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilledLocality {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub city: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_prov: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// Distance from the location to the city the fields came from.
+    pub distance_km: f64,
+}
 
 /// A team enriched with a geocoded location and its list of events for the season.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +40,30 @@ pub struct TeamData {
     pub tba: TbaTeam,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ignore: Option<bool>,
+    /// How the location was matched, when it came from a fresh geocode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geocode_match: Option<GeocodeMatch>,
+    /// Step of the resolution chain that placed the object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_source: Option<LocationSource>,
+    /// Step that originally resolved the location, when `location_source`
+    /// reused it, e.g. `geocode` for an `archive` location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_by: Option<LocationSource>,
+    /// Rough accuracy radius of the location, in metres, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_accuracy: Option<u32>,
+    /// Locality from reverse geocoding, when TBA's is missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filled_locality: Option<FilledLocality>,
+    /// Other teams placed at the same location, spread out for display.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colocated_with: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_offset: Option<DisplayOffset>,
+    /// Where the canonical location came from; recorded in the archive only.
+    #[serde(skip)]
+    pub provenance: Option<Provenance>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub events: Vec<String>,
 }
@@ -18,6 +73,14 @@ impl TeamData {
         Self {
             tba,
             ignore: None,
+            geocode_match: None,
+            location_source: None,
+            resolved_by: None,
+            location_accuracy: None,
+            filled_locality: None,
+            colocated_with: Vec::new(),
+            display_offset: None,
+            provenance: None,
             events: Vec::new(),
         }
     }
@@ -32,6 +95,27 @@ pub struct EventData {
     pub is_official: bool,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub ignore: Option<bool>,
+    /// How the location was matched, when it came from a fresh geocode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geocode_match: Option<GeocodeMatch>,
+    /// Step of the resolution chain that placed the object.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_source: Option<LocationSource>,
+    /// Step that originally resolved the location, when `location_source`
+    /// reused it, e.g. `geocode` for an `archive` location.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolved_by: Option<LocationSource>,
+    /// Rough accuracy radius of the location, in metres, when known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location_accuracy: Option<u32>,
+    /// Other events held at the same location, spread out for display.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colocated_with: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_offset: Option<DisplayOffset>,
+    /// Where the canonical location came from; recorded in the archive only.
+    #[serde(skip)]
+    pub provenance: Option<Provenance>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub teams: Vec<String>,
 }
@@ -45,19 +129,64 @@ impl EventData {
             is_cmp,
             is_official,
             ignore: None,
+            geocode_match: None,
+            location_source: None,
+            resolved_by: None,
+            location_accuracy: None,
+            colocated_with: Vec::new(),
+            display_offset: None,
+            provenance: None,
             teams: Vec::new(),
         }
     }
 }
 
 /// Abstracts lat/lng access so geocoder helpers can be generic over both
-/// `TeamData` and `EventData`.
+/// `TeamData` and `EventData`. `lat`/`lng` are always the canonical location;
+/// display tweaks go through `set_display_offset`.
 pub trait HasLocation {
     fn lat(&self) -> Option<f64>;
     fn lng(&self) -> Option<f64>;
     fn set_lat_lng(&mut self, lat: f64, lng: f64);
+    /// Clear the canonical location along with any display offset, source and
+    /// provenance.
     fn clear_location(&mut self);
+    fn set_display_offset(&mut self, offset: Option<DisplayOffset>);
     fn set_ignore(&mut self, val: bool);
+    fn is_ignored(&self) -> bool;
+    fn set_geocode_match(&mut self, source: GeocodeMatch);
+    /// Record the step that placed the object, the step that first resolved
+    /// the location, and its accuracy radius.
+    fn set_location_source(
+        &mut self,
+        source: LocationSource,
+        resolved_by: Option<LocationSource>,
+        accuracy: Option<u32>,
+    );
+    fn set_colocated_with(&mut self, keys: Vec<String>);
+    fn provenance(&self) -> Option<&Provenance>;
+    fn set_provenance(&mut self, provenance: Provenance);
+    /// Normalized address used for geocoding.
+    fn address(&self) -> Option<String>;
+    fn place_id(&self) -> Option<String>;
+    /// Postal code, city, state and country for gazetteer lookups.
+    fn locality(&self) -> Locality<'_>;
+    /// Whether paid lookups (place id, FIRST API, geocoding) may be used.
+    fn allow_paid_lookup(&self) -> bool {
+        true
+    }
+    /// FIRST event code, for objects that can be enhanced from the FIRST API.
+    fn first_event_code(&self) -> Option<String> {
+        None
+    }
+    /// Whether the object names a venue, so it can be matched in the venue registry.
+    fn has_venue(&self) -> bool {
+        false
+    }
+    /// Mutable venue and street address, for FIRST API enhancement.
+    fn venue_address_mut(&mut self) -> Option<(&mut Option<String>, &mut Option<String>)> {
+        None
+    }
     fn has_location(&self) -> bool {
         self.lat().is_some() && self.lng().is_some()
     }
@@ -77,10 +206,57 @@ impl HasLocation for TeamData {
     fn clear_location(&mut self) {
         self.tba.lat = None;
         self.tba.lng = None;
+        self.display_offset = None;
+        self.location_source = None;
+        self.resolved_by = None;
+        self.location_accuracy = None;
+        self.provenance = None;
+    }
+    fn set_display_offset(&mut self, offset: Option<DisplayOffset>) {
+        self.display_offset = offset;
     }
     fn set_ignore(&mut self, val: bool) {
         self.ignore = Some(val);
     }
+    fn is_ignored(&self) -> bool {
+        self.ignore == Some(true)
+    }
+    fn set_geocode_match(&mut self, source: GeocodeMatch) {
+        self.geocode_match = Some(source);
+    }
+    fn set_location_source(
+        &mut self,
+        source: LocationSource,
+        resolved_by: Option<LocationSource>,
+        accuracy: Option<u32>,
+    ) {
+        self.location_source = Some(source);
+        self.resolved_by = resolved_by.filter(|&by| by != source);
+        self.location_accuracy = accuracy;
+    }
+    fn set_colocated_with(&mut self, keys: Vec<String>) {
+        self.colocated_with = keys;
+    }
+    fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+    fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = Some(provenance);
+    }
+    fn address(&self) -> Option<String> {
+        make_team_address(&self.tba)
+    }
+    fn place_id(&self) -> Option<String> {
+        self.tba.gmaps_place_id.clone()
+    }
+    fn locality(&self) -> Locality<'_> {
+        Locality {
+            postal_code: self.tba.postal_code.as_deref(),
+            city: self.tba.city.as_deref(),
+            state_prov: self.tba.state_prov.as_deref(),
+            country: self.tba.country.as_deref(),
+        }
+    }
 }
 
 impl HasLocation for EventData {
@@ -97,8 +273,70 @@ impl HasLocation for EventData {
     fn clear_location(&mut self) {
         self.tba.lat = None;
         self.tba.lng = None;
+        self.display_offset = None;
+        self.location_source = None;
+        self.resolved_by = None;
+        self.location_accuracy = None;
+        self.provenance = None;
+    }
+    fn set_display_offset(&mut self, offset: Option<DisplayOffset>) {
+        self.display_offset = offset;
     }
     fn set_ignore(&mut self, val: bool) {
         self.ignore = Some(val);
     }
+    fn is_ignored(&self) -> bool {
+        self.ignore == Some(true)
+    }
+    fn set_geocode_match(&mut self, source: GeocodeMatch) {
+        self.geocode_match = Some(source);
+    }
+    fn set_location_source(
+        &mut self,
+        source: LocationSource,
+        resolved_by: Option<LocationSource>,
+        accuracy: Option<u32>,
+    ) {
+        self.location_source = Some(source);
+        self.resolved_by = resolved_by.filter(|&by| by != source);
+        self.location_accuracy = accuracy;
+    }
+    fn set_colocated_with(&mut self, keys: Vec<String>) {
+        self.colocated_with = keys;
+    }
+    fn provenance(&self) -> Option<&Provenance> {
+        self.provenance.as_ref()
+    }
+    fn set_provenance(&mut self, provenance: Provenance) {
+        self.provenance = Some(provenance);
+    }
+    fn address(&self) -> Option<String> {
+        make_event_address(&self.tba)
+    }
+    fn place_id(&self) -> Option<String> {
+        self.tba.gmaps_place_id.clone()
+    }
+    fn locality(&self) -> Locality<'_> {
+        Locality {
+            postal_code: self.tba.postal_code.as_deref(),
+            city: self.tba.city.as_deref(),
+            state_prov: self.tba.state_prov.as_deref(),
+            country: self.tba.country.as_deref(),
+        }
+    }
+    fn allow_paid_lookup(&self) -> bool {
+        self.is_official
+    }
+    fn first_event_code(&self) -> Option<String> {
+        self.tba.first_event_code.clone()
+    }
+    fn has_venue(&self) -> bool {
+        self.tba
+            .venue
+            .as_deref()
+            .is_some_and(|v| !v.trim().is_empty())
+    }
+    fn venue_address_mut(&mut self) -> Option<(&mut Option<String>, &mut Option<String>)> {
+        Some((&mut self.tba.venue, &mut self.tba.address))
+    }
 }